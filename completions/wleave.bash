@@ -19,7 +19,7 @@ _wleave() {
 
     case "${cmd}" in
         wleave)
-            opts="-v -l -C -b -c -r -m -L -R -T -B -d -f -k -p -h --version --layout --css --buttons-per-row --column-spacing --row-spacing --margin --margin-left --margin-right --margin-top --margin-bottom --delay-command-ms --close-on-lost-focus --show-keybinds --protocol --help"
+            opts="-v -l -C -b -c -r -m -L -R -T -B -d -f -k -p -h --version --layout --layout-command --strict --css --no-default-css --buttons-per-row --min-button-width --column-spacing --row-spacing --margin --margin-left --margin-right --margin-top --margin-bottom --margin-percent --margin-left-percent --margin-right-percent --margin-top-percent --margin-bottom-percent --xdg-margin --xdg-margin-percent --layer-shell-margin --layer-shell-margin-percent --delay-command-ms --startup-command-timeout-ms --pre-action --wait-pre-action --post-action --notify-command --close-on-lost-focus --close-on-right-click --close-on-click-away --show-keybinds --keybind-style --keybind-format --wrap --ellipsize --icon-size --keybind-confirm --keybind-confirm-timeout-ms --icon-position --icon-only --no-icons --cancel-keys --focus-default --help-key --cursor --scroll-nav --swipe-close-distance --ordinal-keybinds --focus-follows-hover --vim-keys --search --keybinds-by-position --keybinds-ignore-case --background-color --background-opacity --timeout-s --timeout-action --timeout-ms --inhibit-idle --close-on-session-lock --animation-ms --reduced-motion --window-opacity --color-scheme --log-file --log-format --daemon --print-config --output --all-outputs --protocol --layer-shell-fallback --geometry --position --anchor --exclusive-zone --help"
             if [[ ${cur} == -* || ${COMP_CWORD} -eq 1 ]] ; then
                 COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
                 return 0
@@ -33,6 +33,10 @@ _wleave() {
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
+                --layout-command)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
                 --css)
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
@@ -49,6 +53,10 @@ _wleave() {
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
+                --min-button-width)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
                 --column-spacing)
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
@@ -105,6 +113,42 @@ _wleave() {
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
+                --margin-percent)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --margin-left-percent)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --margin-right-percent)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --margin-top-percent)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --margin-bottom-percent)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --xdg-margin)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --xdg-margin-percent)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --layer-shell-margin)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --layer-shell-margin-percent)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
                 --delay-command-ms)
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
@@ -113,12 +157,160 @@ _wleave() {
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
+                --startup-command-timeout-ms)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --pre-action)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --post-action)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --notify-command)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --close-on-right-click)
+                    COMPREPLY=($(compgen -W "true false" -- "${cur}"))
+                    return 0
+                    ;;
+                --close-on-click-away)
+                    COMPREPLY=($(compgen -W "true false" -- "${cur}"))
+                    return 0
+                    ;;
+                --keybind-style)
+                    COMPREPLY=($(compgen -W "brackets mnemonic" -- "${cur}"))
+                    return 0
+                    ;;
+                --keybind-format)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --ellipsize)
+                    COMPREPLY=($(compgen -W "none start middle end" -- "${cur}"))
+                    return 0
+                    ;;
+                --icon-size)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --keybind-confirm-timeout-ms)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --icon-position)
+                    COMPREPLY=($(compgen -W "top bottom left right" -- "${cur}"))
+                    return 0
+                    ;;
+                --cancel-keys)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --focus-default)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --help-key)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --cursor)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --swipe-close-distance)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --focus-follows-hover)
+                    COMPREPLY=($(compgen -W "true false" -- "${cur}"))
+                    return 0
+                    ;;
+                --keybinds-ignore-case)
+                    COMPREPLY=($(compgen -W "true false" -- "${cur}"))
+                    return 0
+                    ;;
+                --background-color)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --background-opacity)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --timeout-s)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --timeout-action)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --timeout-ms)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --inhibit-idle)
+                    COMPREPLY=($(compgen -W "true false" -- "${cur}"))
+                    return 0
+                    ;;
+                --close-on-session-lock)
+                    COMPREPLY=($(compgen -W "true false" -- "${cur}"))
+                    return 0
+                    ;;
+                --animation-ms)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --window-opacity)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --color-scheme)
+                    COMPREPLY=($(compgen -W "default light dark" -- "${cur}"))
+                    return 0
+                    ;;
+                --log-file)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --log-format)
+                    COMPREPLY=($(compgen -W "text json" -- "${cur}"))
+                    return 0
+                    ;;
+                --output)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
                 --protocol)
-                    COMPREPLY=($(compgen -W "layer-shell xdg" -- "${cur}"))
+                    COMPREPLY=($(compgen -W "auto layer-shell xdg" -- "${cur}"))
                     return 0
                     ;;
                 -p)
-                    COMPREPLY=($(compgen -W "layer-shell xdg" -- "${cur}"))
+                    COMPREPLY=($(compgen -W "auto layer-shell xdg" -- "${cur}"))
+                    return 0
+                    ;;
+                --layer-shell-fallback)
+                    COMPREPLY=($(compgen -W "true false" -- "${cur}"))
+                    return 0
+                    ;;
+                --geometry)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --position)
+                    COMPREPLY=($(compgen -W "center top bottom left right top-left top-right bottom-left bottom-right" -- "${cur}"))
+                    return 0
+                    ;;
+                --anchor)
+                    COMPREPLY=($(compgen -W "left right top bottom" -- "${cur}"))
+                    return 0
+                    ;;
+                --exclusive-zone)
+                    COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
                 *)
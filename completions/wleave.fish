@@ -1,16 +1,80 @@
 complete -c wleave -s l -l layout -d 'Specify a layout file' -r -F
+complete -c wleave -l layout-command -d 'Run a shell command and use its output as the layout' -r
 complete -c wleave -s C -l css -d 'Specify a custom CSS file' -r -F
 complete -c wleave -s b -l buttons-per-row -d 'Set the number of buttons per row' -r
+complete -c wleave -l min-button-width -d 'Use fewer buttons per row when they would be narrower than this (in pixels)' -r
 complete -c wleave -s c -l column-spacing -d 'Set space between buttons columns' -r
 complete -c wleave -s r -l row-spacing -d 'Set space between buttons rows' -r
-complete -c wleave -s m -l margin -d 'Set the margin around buttons' -r
+complete -c wleave -s m -l margin -d 'Set the margin around buttons (230 by default, 0 with --geometry)' -r
 complete -c wleave -s L -l margin-left -d 'Set margin for the left of buttons' -r
 complete -c wleave -s R -l margin-right -d 'Set margin for the right of buttons' -r
 complete -c wleave -s T -l margin-top -d 'Set margin for the top of buttons' -r
 complete -c wleave -s B -l margin-bottom -d 'Set the margin for the bottom of buttons' -r
+complete -c wleave -l margin-percent -d 'Set the margin around buttons as a percentage of the monitor size' -r
+complete -c wleave -l margin-left-percent -d 'Set margin for the left of buttons as a percentage of the monitor width' -r
+complete -c wleave -l margin-right-percent -d 'Set margin for the right of buttons as a percentage of the monitor width' -r
+complete -c wleave -l margin-top-percent -d 'Set margin for the top of buttons as a percentage of the monitor height' -r
+complete -c wleave -l margin-bottom-percent -d 'Set margin for the bottom of buttons as a percentage of the monitor height' -r
+complete -c wleave -l xdg-margin -d 'Set the margin around buttons with the xdg protocol, overriding --margin' -r
+complete -c wleave -l xdg-margin-percent -d 'Set the xdg margin around buttons as a percentage of the monitor size' -r
+complete -c wleave -l layer-shell-margin -d 'Set the margin around buttons with the layer-shell protocol, overriding --margin' -r
+complete -c wleave -l layer-shell-margin-percent -d 'Set the layer-shell margin around buttons as a percentage of the monitor size' -r
 complete -c wleave -s d -l delay-command-ms -d 'The delay (in milliseconds) between the window closing and executing the selected option' -r
-complete -c wleave -s p -l protocol -d 'Use layer-shell or xdg protocol' -r -f -a "{layer-shell	,xdg	}"
+complete -c wleave -l startup-command-timeout-ms -d 'The maximum time (in milliseconds) to wait for the text and enabled commands of buttons' -r
+complete -c wleave -l pre-action -d 'A command to run before the selected action, right after the window is hidden' -r
+complete -c wleave -l post-action -d 'A command to run once the selected action exits' -r
+complete -c wleave -l notify-command -d 'A command to run whenever a command of the selected action is started' -r
+complete -c wleave -l close-on-right-click -d 'Close the menu on a right click outside of the buttons' -r -f -a "{true	,false	}"
+complete -c wleave -l close-on-click-away -d 'Close the menu on a click outside of the buttons' -r -f -a "{true	,false	}"
+complete -c wleave -l keybind-style -d 'Show the key binds in brackets after the text or underline them within it' -r -f -a "{brackets	,mnemonic	}"
+complete -c wleave -l keybind-format -d 'How the brackets style shows key binds, {keybind} is replaced with the key bind' -r
+complete -c wleave -l ellipsize -d 'Shorten long button texts with an ellipsis at the given place' -r -f -a "{none	,start	,middle	,end	}"
+complete -c wleave -l icon-size -d 'Set the size (in pixels) of button icons' -r
+complete -c wleave -l keybind-confirm-timeout-ms -d 'The time (in milliseconds) to press a keybind again with --keybind-confirm' -r
+complete -c wleave -l icon-position -d 'Set the position of button icons relative to their text' -r -f -a "{top	,bottom	,left	,right	}"
+complete -c wleave -l cancel-keys -d 'The keys that close the menu (comma-separated)' -r
+complete -c wleave -l focus-default -d 'The button focused when the menu opens: a label, a position starting at 1, "first" or "none"' -r
+complete -c wleave -l help-key -d 'The key that toggles an overlay listing every keybind' -r
+complete -c wleave -l cursor -d 'Set the pointer cursor shown over the menu by name, or hide it with "none"' -r
+complete -c wleave -l swipe-close-distance -d 'Close the menu when swiping down on a touchscreen by at least the given distance (in pixels)' -r
+complete -c wleave -l focus-follows-hover -d 'Move the keyboard focus to the button under the pointer' -r -f -a "{true	,false	}"
+complete -c wleave -l keybinds-ignore-case -d 'Match keybinds regardless of case, so that Caps Lock and Shift do not matter' -r -f -a "{true	,false	}"
+complete -c wleave -l background-color -d 'Set the color of the window background, e.g. black or #1e1e1e' -r
+complete -c wleave -l background-opacity -d 'Set the opacity of the window background from 0.0 to 1.0' -r
+complete -c wleave -l timeout-s -d 'Close the menu after this many seconds without input, 0 to keep it open' -r
+complete -c wleave -l timeout-action -d 'Run the button with this label instead of closing the menu once --timeout-s elapses' -r
+complete -c wleave -l timeout-ms -d 'Close the menu after this many milliseconds, even while it is used, 0 to keep it open' -r
+complete -c wleave -l inhibit-idle -d 'Keep the session from going idle while the menu is shown' -r -f -a "{true	,false	}"
+complete -c wleave -l close-on-session-lock -d 'Close the menu when logind locks the session' -r -f -a "{true	,false	}"
+complete -c wleave -l animation-ms -d 'Fade the menu in over this many milliseconds, 0 to show it right away' -r
+complete -c wleave -l window-opacity -d 'Set the opacity of the whole window, including the buttons, from 0.0 to 1.0' -r
+complete -c wleave -l color-scheme -d 'Force a light or dark variant of the GTK theme' -r -f -a "{default	,light	,dark	}"
+complete -c wleave -l log-file -d 'Also append diagnostics to the given file, rotating it once it grows past 1 MiB' -r -F
+complete -c wleave -l log-format -d 'Print diagnostics as plain text or as JSON lines' -r -f -a "{text	Plain lines of text,json	One JSON object per line}"
+complete -c wleave -l output -d 'Show the menu on the output given by its number or a part of its manufacturer and model' -r
+complete -c wleave -s p -l protocol -d 'Use layer-shell or xdg protocol, or pick one depending on the display' -r -f -a "{auto	Layer-shell when the display supports it\, xdg otherwise\, decided at startup,layer-shell	,xdg	}"
+complete -c wleave -l layer-shell-fallback -d 'Fall back to the xdg protocol when the compositor does not support layer-shell' -r -f -a "{true	,false	}"
+complete -c wleave -l geometry -d 'Open a window of the given size (WxH) instead of a fullscreen one' -r
+complete -c wleave -l position -d 'Place the layer-shell surface at the center, an edge or a corner of the output' -r -f -a "{center	,top	,bottom	,left	,right	,top-left	,top-right	,bottom-left	,bottom-right	}"
+complete -c wleave -l anchor -d 'Anchor the layer-shell surface to the given edges (comma-separated)' -r -f -a "{left	,right	,top	,bottom	}"
+complete -c wleave -l exclusive-zone -d 'Set the layer-shell exclusive zone, -1 to overlay other surfaces' -r
 complete -c wleave -s v -l version
+complete -c wleave -l strict -d 'Treat unknown fields in the layout as errors instead of warnings'
+complete -c wleave -l no-default-css -d 'Skip the system-wide style.css, only loading the --css file or the one in the user config'
+complete -c wleave -l wait-pre-action -d 'Wait for the pre-action to exit before starting the delay'
 complete -c wleave -s f -l close-on-lost-focus -d 'Close the menu on lost focus'
 complete -c wleave -s k -l show-keybinds -d 'Show the associated key binds'
-complete -c wleave -s h -l help -d 'Print help'
+complete -c wleave -l wrap -d 'Wrap long button texts over several lines'
+complete -c wleave -l keybind-confirm -d 'Require pressing a keybind twice to select its button'
+complete -c wleave -l icon-only -d 'Only show the icon of buttons that have one, keeping the text as their tooltip'
+complete -c wleave -l no-icons -d 'Do not load the icons of the buttons, only showing their text'
+complete -c wleave -l scroll-nav -d 'Move the focus between buttons with the scroll wheel'
+complete -c wleave -l ordinal-keybinds -d 'Select the first nine buttons with the digits 1 to 9'
+complete -c wleave -l vim-keys -d 'Move the focus with h, j, k and l, and jump to the first or last button with gg and G'
+complete -c wleave -l search -d 'Filter the buttons by typing instead of using their keybinds'
+complete -c wleave -l keybinds-by-position -d 'Match keybinds by the position of the key, as in the first Latin keyboard layout'
+complete -c wleave -l reduced-motion -d 'Turn off animations even when the GTK settings enable them'
+complete -c wleave -l daemon -d 'Keep running in the background, showing the menu when invoked again'
+complete -c wleave -l print-config -d 'Print the configuration merged from the options and the layout file as JSON, then exit'
+complete -c wleave -l all-outputs -d 'Cover the other outputs with the background of the menu, with the layer-shell protocol'
+complete -c wleave -s h -l help -d 'Print help (see more with \'--help\')'
@@ -1,12 +1,162 @@
 use clap::{ArgAction, Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::str::FromStr;
 
-#[derive(Debug, Copy, Clone, ValueEnum)]
+#[derive(Debug, Copy, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Protocol {
+    /// Layer-shell when the display supports it, xdg otherwise, decided at startup
+    Auto,
     LayerShell,
     Xdg,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// A place on the output for a compact layer-shell surface
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Position {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Position {
+    /// The edges the surface is anchored to, none for the center
+    pub fn anchor(self) -> Vec<Edge> {
+        match self {
+            Position::Center => vec![],
+            Position::Top => vec![Edge::Top],
+            Position::Bottom => vec![Edge::Bottom],
+            Position::Left => vec![Edge::Left],
+            Position::Right => vec![Edge::Right],
+            Position::TopLeft => vec![Edge::Top, Edge::Left],
+            Position::TopRight => vec![Edge::Top, Edge::Right],
+            Position::BottomLeft => vec![Edge::Bottom, Edge::Left],
+            Position::BottomRight => vec![Edge::Bottom, Edge::Right],
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeybindStyle {
+    Brackets,
+    Mnemonic,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ellipsize {
+    None,
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IconPosition {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// A window size in the `WxH` format
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct Geometry {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl FromStr for Geometry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected WxH, got {s}"))?;
+
+        let parse_dimension = |dimension: &str| match dimension.trim().parse::<i32>() {
+            Ok(value) if value > 0 => Ok(value),
+            _ => Err(format!("invalid dimension {dimension} in {s}")),
+        };
+
+        Ok(Geometry {
+            width: parse_dimension(width)?,
+            height: parse_dimension(height)?,
+        })
+    }
+}
+
+/// The button focused when the menu opens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FocusTarget {
+    None,
+    First,
+    /// A position in display order, starting at 1
+    Index(usize),
+    Label(String),
+}
+
+impl FromStr for FocusTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "none" => FocusTarget::None,
+            "first" => FocusTarget::First,
+            _ => match s.parse::<usize>() {
+                Ok(0) => return Err("button positions start at 1".to_owned()),
+                Ok(index) => FocusTarget::Index(index),
+                Err(_) => FocusTarget::Label(s.to_owned()),
+            },
+        })
+    }
+}
+
+/// Written back in the form accepted by `--focus-default`
+impl Serialize for FocusTarget {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            FocusTarget::None => serializer.serialize_str("none"),
+            FocusTarget::First => serializer.serialize_str("first"),
+            FocusTarget::Index(index) => serializer.serialize_str(&index.to_string()),
+            FocusTarget::Label(label) => serializer.serialize_str(label),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub enum ColorScheme {
+    Default,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Plain lines of text
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, disable_version_flag = true, about, long_about = None)]
 pub struct Args {
@@ -17,14 +167,30 @@ pub struct Args {
     #[arg(short = 'l', long)]
     pub layout: Option<PathBuf>,
 
+    /// Run a shell command and use its output as the layout
+    #[arg(long, conflicts_with = "layout")]
+    pub layout_command: Option<String>,
+
+    /// Treat unknown fields in the layout as errors instead of warnings
+    #[arg(long)]
+    pub strict: bool,
+
     /// Specify a custom CSS file
     #[arg(short = 'C', long)]
     pub css: Option<PathBuf>,
 
+    /// Skip the system-wide style.css, only loading the --css file or the one in the user config
+    #[arg(long)]
+    pub no_default_css: bool,
+
     /// Set the number of buttons per row
     #[arg(short = 'b', long = "buttons-per-row", default_value_t = 3)]
     pub buttons_per_row: u32,
 
+    /// Use fewer buttons per row when they would be narrower than this (in pixels)
+    #[arg(long)]
+    pub min_button_width: Option<u32>,
+
     /// Set space between buttons columns
     #[arg(short = 'c', long = "column-spacing", default_value_t = 5)]
     pub column_spacing: u32,
@@ -33,9 +199,9 @@ pub struct Args {
     #[arg(short = 'r', long = "row-spacing", default_value_t = 5)]
     pub row_spacing: u32,
 
-    /// Set the margin around buttons
-    #[arg(short = 'm', long, default_value_t = 230)]
-    pub margin: i32,
+    /// Set the margin around buttons (230 by default, 0 with --geometry)
+    #[arg(short = 'm', long)]
+    pub margin: Option<i32>,
 
     /// Set margin for the left of buttons
     #[arg(short = 'L', long)]
@@ -53,19 +219,269 @@ pub struct Args {
     #[arg(short = 'B', long)]
     pub margin_bottom: Option<i32>,
 
+    /// Set the margin around buttons as a percentage of the monitor size
+    #[arg(long)]
+    pub margin_percent: Option<f64>,
+
+    /// Set margin for the left of buttons as a percentage of the monitor width
+    #[arg(long)]
+    pub margin_left_percent: Option<f64>,
+
+    /// Set margin for the right of buttons as a percentage of the monitor width
+    #[arg(long)]
+    pub margin_right_percent: Option<f64>,
+
+    /// Set margin for the top of buttons as a percentage of the monitor height
+    #[arg(long)]
+    pub margin_top_percent: Option<f64>,
+
+    /// Set margin for the bottom of buttons as a percentage of the monitor height
+    #[arg(long)]
+    pub margin_bottom_percent: Option<f64>,
+
+    /// Set the margin around buttons with the xdg protocol, overriding --margin
+    #[arg(long)]
+    pub xdg_margin: Option<i32>,
+
+    /// Set the xdg margin around buttons as a percentage of the monitor size
+    #[arg(long)]
+    pub xdg_margin_percent: Option<f64>,
+
+    /// Set the margin around buttons with the layer-shell protocol, overriding --margin
+    #[arg(long)]
+    pub layer_shell_margin: Option<i32>,
+
+    /// Set the layer-shell margin around buttons as a percentage of the monitor size
+    #[arg(long)]
+    pub layer_shell_margin_percent: Option<f64>,
+
     /// The delay (in milliseconds) between the window closing and executing the selected option
     #[arg(short = 'd', long, default_value_t = 100)]
     pub delay_command_ms: u32,
 
+    /// The maximum time (in milliseconds) to wait for the text and enabled commands of buttons
+    #[arg(long, default_value_t = 500)]
+    pub startup_command_timeout_ms: u32,
+
+    /// A command to run before the selected action, right after the window is hidden
+    #[arg(long)]
+    pub pre_action: Option<String>,
+
+    /// Wait for the pre-action to exit before starting the delay
+    #[arg(long)]
+    pub wait_pre_action: bool,
+
+    /// A command to run once the selected action exits
+    #[arg(long)]
+    pub post_action: Option<String>,
+
+    /// A command to run whenever a command of the selected action is started
+    #[arg(long)]
+    pub notify_command: Option<String>,
+
     /// Close the menu on lost focus
     #[arg(short = 'f', long)]
     pub close_on_lost_focus: bool,
 
+    /// Close the menu on a right click outside of the buttons
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub close_on_right_click: bool,
+
+    /// Close the menu on a click outside of the buttons
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub close_on_click_away: bool,
+
     /// Show the associated key binds
     #[arg(short = 'k', long)]
     pub show_keybinds: bool,
 
-    /// Use layer-shell or xdg protocol
-    #[arg(short = 'p', long, value_enum, default_value_t = Protocol::Xdg)]
+    /// Show the key binds in brackets after the text or underline them within it
+    #[arg(long, value_enum, default_value_t = KeybindStyle::Brackets)]
+    pub keybind_style: KeybindStyle,
+
+    /// How the brackets style shows key binds, {keybind} is replaced with the key bind
+    #[arg(long, default_value = "[{keybind}]")]
+    pub keybind_format: String,
+
+    /// Wrap long button texts over several lines
+    #[arg(long)]
+    pub wrap: bool,
+
+    /// Shorten long button texts with an ellipsis at the given place
+    #[arg(long, value_enum, default_value_t = Ellipsize::None)]
+    pub ellipsize: Ellipsize,
+
+    /// Set the size (in pixels) of button icons
+    #[arg(long, default_value_t = 96)]
+    pub icon_size: i32,
+
+    /// Require pressing a keybind twice to select its button
+    #[arg(long)]
+    pub keybind_confirm: bool,
+
+    /// The time (in milliseconds) to press a keybind again with --keybind-confirm
+    #[arg(long, default_value_t = 2000)]
+    pub keybind_confirm_timeout_ms: u32,
+
+    /// Set the position of button icons relative to their text
+    #[arg(long, value_enum, default_value_t = IconPosition::Top)]
+    pub icon_position: IconPosition,
+
+    /// Only show the icon of buttons that have one, keeping the text as their tooltip
+    #[arg(long)]
+    pub icon_only: bool,
+
+    /// Do not load the icons of the buttons, only showing their text
+    #[arg(long, conflicts_with = "icon_only")]
+    pub no_icons: bool,
+
+    /// The keys that close the menu (comma-separated)
+    #[arg(long, value_delimiter = ',', default_value = "Escape")]
+    pub cancel_keys: Vec<String>,
+
+    /// The button focused when the menu opens: a label, a position starting at 1, "first" or "none"
+    #[arg(long)]
+    pub focus_default: Option<FocusTarget>,
+
+    /// The key that toggles an overlay listing every keybind
+    #[arg(long, default_value = "?")]
+    pub help_key: String,
+
+    /// Set the pointer cursor shown over the menu by name, or hide it with "none"
+    #[arg(long)]
+    pub cursor: Option<String>,
+
+    /// Move the focus between buttons with the scroll wheel
+    #[arg(long)]
+    pub scroll_nav: bool,
+
+    /// Close the menu when swiping down on a touchscreen by at least the given distance (in pixels)
+    #[arg(long)]
+    pub swipe_close_distance: Option<u32>,
+
+    /// Select the first nine buttons with the digits 1 to 9
+    #[arg(long)]
+    pub ordinal_keybinds: bool,
+
+    /// Move the keyboard focus to the button under the pointer
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub focus_follows_hover: bool,
+
+    /// Move the focus with h, j, k and l, and jump to the first or last button with gg and G
+    #[arg(long)]
+    pub vim_keys: bool,
+
+    /// Filter the buttons by typing instead of using their keybinds
+    #[arg(long)]
+    pub search: bool,
+
+    /// Match keybinds by the position of the key, as in the first Latin keyboard layout
+    #[arg(long)]
+    pub keybinds_by_position: bool,
+
+    /// Match keybinds regardless of case, so that Caps Lock and Shift do not matter
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub keybinds_ignore_case: bool,
+
+    /// Set the color of the window background, e.g. black or #1e1e1e
+    #[arg(long)]
+    pub background_color: Option<String>,
+
+    /// Set the opacity of the window background from 0.0 to 1.0
+    #[arg(long)]
+    pub background_opacity: Option<f64>,
+
+    /// Close the menu after this many seconds without input, 0 to keep it open
+    #[arg(long)]
+    pub timeout_s: Option<u32>,
+
+    /// Run the button with this label instead of closing the menu once --timeout-s elapses
+    #[arg(long, requires = "timeout_s")]
+    pub timeout_action: Option<String>,
+
+    /// Close the menu after this many milliseconds, even while it is used, 0 to keep it open
+    #[arg(long)]
+    pub timeout_ms: Option<u32>,
+
+    /// Keep the session from going idle while the menu is shown
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub inhibit_idle: bool,
+
+    /// Close the menu when logind locks the session
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub close_on_session_lock: bool,
+
+    /// Fade the menu in over this many milliseconds, 0 to show it right away
+    #[arg(long, default_value_t = 0)]
+    pub animation_ms: u32,
+
+    /// Turn off animations even when the GTK settings enable them
+    #[arg(long)]
+    pub reduced_motion: bool,
+
+    /// Set the opacity of the whole window, including the buttons, from 0.0 to 1.0
+    #[arg(long, default_value_t = 1.0)]
+    pub window_opacity: f64,
+
+    /// Force a light or dark variant of the GTK theme
+    #[arg(long, value_enum, default_value_t = ColorScheme::Default)]
+    pub color_scheme: ColorScheme,
+
+    /// Also append diagnostics to the given file, rotating it once it grows past 1 MiB
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Print diagnostics as plain text or as JSON lines
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Keep running in the background, showing the menu when invoked again
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Print the configuration merged from the options and the layout file as JSON, then exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Show the menu on the output given by its number or a part of its manufacturer and model
+    #[arg(long)]
+    pub output: Option<String>,
+
+    /// Cover the other outputs with the background of the menu, with the layer-shell protocol
+    #[arg(long)]
+    pub all_outputs: bool,
+
+    /// Use layer-shell or xdg protocol, or pick one depending on the display
+    #[arg(short = 'p', long, value_enum, default_value_t = Protocol::Auto)]
     pub protocol: Protocol,
+
+    /// Fall back to the xdg protocol when the compositor does not support layer-shell
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub layer_shell_fallback: bool,
+
+    /// Open a window of the given size (WxH) instead of a fullscreen one
+    #[arg(long)]
+    pub geometry: Option<Geometry>,
+
+    /// Place the layer-shell surface at the center, an edge or a corner of the output
+    #[arg(long, value_enum, conflicts_with = "anchor")]
+    pub position: Option<Position>,
+
+    /// Anchor the layer-shell surface to the given edges (comma-separated)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom]
+    )]
+    pub anchor: Vec<Edge>,
+
+    /// Set the layer-shell exclusive zone, -1 to overlay other surfaces
+    #[arg(
+        long,
+        default_value_t = -1,
+        allow_negative_numbers = true,
+        value_parser = clap::value_parser!(i32).range(-1..)
+    )]
+    pub exclusive_zone: i32,
 }
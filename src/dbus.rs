@@ -0,0 +1,112 @@
+//! Optional D-Bus integration for long-running instances.
+//!
+//! The `show` and `hide` actions are exported by GApplication on the
+//! `org.gtk.Actions` interface of `/sh/natty/Wleave`, so a running instance
+//! can be toggled with e.g. `gapplication action sh.natty.Wleave show`.
+//! A hidden instance keeps running until it is shown again.
+//! The same object also implements the `sh.natty.Wleave` interface, whose
+//! `Show` and `Hide` methods activate these actions and whose `Selected`
+//! signal reports the chosen button.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::gio::{ApplicationHoldGuard, DBusNodeInfo, SimpleAction};
+use gtk::glib::{SendWeakRef, ToVariant};
+use gtk::prelude::*;
+use gtk::Application;
+
+pub const INTERFACE: &str = "sh.natty.Wleave";
+
+const INTROSPECTION: &str = r#"<node>
+  <interface name="sh.natty.Wleave">
+    <method name="Show"/>
+    <method name="Hide"/>
+    <signal name="Selected">
+      <arg name="label" type="s"/>
+    </signal>
+  </interface>
+</node>"#;
+
+pub fn register_actions(app: &Application, show: impl Fn(&Application) + 'static) {
+    // Keeps the application running while hidden, until the menu is shown again
+    let hidden: Rc<RefCell<Option<ApplicationHoldGuard>>> = Rc::default();
+
+    let show_action = SimpleAction::new("show", None);
+    let app_handle = app.downgrade();
+    let hidden_handle = hidden.clone();
+    show_action.connect_activate(move |_, _| {
+        if let Some(app) = app_handle.upgrade() {
+            show(&app);
+            hidden_handle.take();
+        }
+    });
+    app.add_action(&show_action);
+
+    let hide_action = SimpleAction::new("hide", None);
+    let app_handle = app.downgrade();
+    hide_action.connect_activate(move |_, _| {
+        if let Some(app) = app_handle.upgrade() {
+            hidden.borrow_mut().get_or_insert_with(|| app.hold());
+
+            for window in app.windows() {
+                window.close();
+            }
+        }
+    });
+    app.add_action(&hide_action);
+
+    if let Err(e) = register_interface(app) {
+        log!("Failed to export the {INTERFACE} interface: {e}");
+    }
+}
+
+/// Exports the `sh.natty.Wleave` interface next to the actions of the application
+fn register_interface(app: &Application) -> Result<(), String> {
+    let (Some(connection), Some(path)) = (app.dbus_connection(), app.dbus_object_path()) else {
+        return Ok(());
+    };
+
+    let interface = DBusNodeInfo::for_xml(INTROSPECTION)
+        .map_err(|e| e.to_string())?
+        .lookup_interface(INTERFACE)
+        .ok_or("The interface is missing from the introspection data")?;
+
+    // Method calls arrive on the main thread, but the handler has to be Send
+    let app_handle = SendWeakRef::from(app.downgrade());
+    connection
+        .register_object(
+            &path,
+            &interface,
+            move |_, _, _, _, method, _, invocation| {
+                let action = if method == "Show" { "show" } else { "hide" };
+                if let Some(app) = app_handle.upgrade() {
+                    app.activate_action(action, None);
+                }
+                invocation.return_value(None);
+            },
+            // The interface has no properties to get or set
+            |_, _, _, _, _| ().to_variant(),
+            |_, _, _, _, _, _| false,
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Emits the `Selected` signal carrying the label of the chosen button
+pub fn emit_selected(app: &Application, label: &str) {
+    let (Some(connection), Some(path)) = (app.dbus_connection(), app.dbus_object_path()) else {
+        return;
+    };
+
+    if let Err(e) = connection.emit_signal(
+        None,
+        &path,
+        INTERFACE,
+        "Selected",
+        Some(&(label,).to_variant()),
+    ) {
+        log!("Failed to emit the Selected signal: {e}");
+    }
+}
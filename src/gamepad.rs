@@ -0,0 +1,90 @@
+//! Optional gamepad input for devices without a keyboard at hand.
+//!
+//! Gamepads are polled from a timer on the GTK main loop. Controllers plugged
+//! in after the menu opened are picked up by gilrs, and without any controller
+//! the poll finds no events and does nothing.
+
+use std::time::Duration;
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+use gtk::glib::{timeout_add_local, ControlFlow};
+use gtk::DirectionType;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How far a stick has to be pushed to move the focus
+const STICK_THRESHOLD: f32 = 0.5;
+
+pub enum Input {
+    Move(DirectionType),
+    Activate,
+    Cancel,
+}
+
+/// Calls `on_input` for every gamepad input until it returns `ControlFlow::Break`
+pub fn watch(mut on_input: impl FnMut(Input) -> ControlFlow + 'static) {
+    let mut gilrs = match Gilrs::new() {
+        Ok(gilrs) => gilrs,
+        Err(e) => {
+            log!("Gamepad input is unavailable: {e}");
+            return;
+        }
+    };
+
+    // Whether each stick axis is pushed, so that holding it moves the focus only once
+    let mut pushed_x = false;
+    let mut pushed_y = false;
+
+    timeout_add_local(POLL_INTERVAL, move || {
+        while let Some(event) = gilrs.next_event() {
+            let input = match event.event {
+                EventType::ButtonPressed(button, _) => match button {
+                    Button::DPadUp => Input::Move(DirectionType::Up),
+                    Button::DPadDown => Input::Move(DirectionType::Down),
+                    Button::DPadLeft => Input::Move(DirectionType::Left),
+                    Button::DPadRight => Input::Move(DirectionType::Right),
+                    Button::South => Input::Activate,
+                    Button::East => Input::Cancel,
+                    _ => continue,
+                },
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => {
+                    match stick_direction(&mut pushed_x, value) {
+                        Some(true) => Input::Move(DirectionType::Right),
+                        Some(false) => Input::Move(DirectionType::Left),
+                        None => continue,
+                    }
+                }
+                EventType::AxisChanged(Axis::LeftStickY, value, _) => {
+                    match stick_direction(&mut pushed_y, value) {
+                        Some(true) => Input::Move(DirectionType::Up),
+                        Some(false) => Input::Move(DirectionType::Down),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            if on_input(input).is_break() {
+                return ControlFlow::Break;
+            }
+        }
+
+        ControlFlow::Continue
+    });
+}
+
+/// Returns the direction of a stick axis when it gets pushed past the threshold,
+/// `true` for the positive end
+fn stick_direction(pushed: &mut bool, value: f32) -> Option<bool> {
+    if value.abs() < STICK_THRESHOLD / 2.0 {
+        *pushed = false;
+        return None;
+    }
+
+    if *pushed || value.abs() < STICK_THRESHOLD {
+        return None;
+    }
+
+    *pushed = true;
+    Some(value > 0.0)
+}
@@ -0,0 +1,78 @@
+//! Diagnostics printed to stderr and, with `--log-file`, appended to a file,
+//! since the output of wleave is often lost when it is launched by a compositor.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use wleave::cli_opt::LogFormat;
+
+/// Log files larger than this are rotated on startup
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+static LOG_FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log::write(format_args!($($arg)*))
+    };
+}
+
+/// Opens the log file, moving it to `PATH.1` first when it grew too large
+pub fn init(path: &Path) -> Result<(), String> {
+    if path.metadata().is_ok_and(|m| m.len() >= MAX_LOG_SIZE) {
+        let mut rotated = PathBuf::from(path).into_os_string();
+        rotated.push(".1");
+
+        std::fs::rename(path, &rotated)
+            .map_err(|e| format!("Failed to rotate the log file {}: {e}", path.display()))?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open the log file {}: {e}", path.display()))?;
+
+    LOG_FILE
+        .set(Mutex::new(file))
+        .map_err(|_| "The log file is already open".to_owned())
+}
+
+/// Sets how the following diagnostics are printed, plain text by default
+pub fn set_format(format: LogFormat) {
+    let _ = LOG_FORMAT.set(format);
+}
+
+pub fn write(args: fmt::Arguments) {
+    let line = match LOG_FORMAT.get() {
+        Some(LogFormat::Json) => json_line(args),
+        Some(LogFormat::Text) | None => args.to_string(),
+    };
+
+    eprintln!("{line}");
+
+    if let Some(file) = LOG_FILE.get() {
+        if let Ok(mut file) = file.lock() {
+            // There is nowhere left to report a failure to write the log
+            let _ = writeln!(file, "{line}");
+        }
+    }
+}
+
+/// Formats a message as a JSON object with the time in seconds since the epoch
+fn json_line(args: fmt::Arguments) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |time| time.as_secs_f64());
+
+    serde_json::json!({
+        "time": time,
+        "message": args.to_string(),
+    })
+    .to_string()
+}
@@ -0,0 +1,60 @@
+//! Closing the menu when logind locks the session, so that it does not linger
+//! above or below the screen locker.
+//!
+//! The session is looked up on the system bus once at startup. Without logind,
+//! for example on systems without systemd, the menu simply stays open.
+
+use gtk::gio::{self, BusType, DBusCallFlags, DBusSignalFlags};
+use gtk::glib::ToVariant;
+
+const LOGIND: &str = "org.freedesktop.login1";
+
+/// Calls `on_lock` whenever the session wleave runs in gets locked
+pub fn watch_lock(on_lock: impl Fn() + 'static) {
+    gio::bus_get(BusType::System, gio::Cancellable::NONE, move |connection| {
+        let connection = match connection {
+            Ok(connection) => connection,
+            Err(e) => {
+                log!("Failed to watch for session locks: {e}");
+                return;
+            }
+        };
+
+        // "auto" is the session of wleave, or else the graphical session of the user
+        connection.clone().call(
+            Some(LOGIND),
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            "GetSession",
+            Some(&("auto",).to_variant()),
+            None,
+            DBusCallFlags::NONE,
+            -1,
+            gio::Cancellable::NONE,
+            move |reply| {
+                let session = match reply {
+                    Ok(reply) => reply.child_value(0),
+                    Err(e) => {
+                        log!("Failed to watch for session locks: {e}");
+                        return;
+                    }
+                };
+
+                let Some(path) = session.str() else {
+                    log!("Failed to watch for session locks: unexpected reply {session}");
+                    return;
+                };
+
+                connection.signal_subscribe(
+                    Some(LOGIND),
+                    Some("org.freedesktop.login1.Session"),
+                    Some("Lock"),
+                    Some(path),
+                    None,
+                    DBusSignalFlags::NONE,
+                    move |_, _, _, _, _, _| on_lock(),
+                );
+            },
+        );
+    });
+}
@@ -1,40 +1,221 @@
+#[macro_use]
+mod log;
+
+#[cfg(feature = "dbus")]
+mod dbus;
+
+#[cfg(feature = "gamepad")]
+mod gamepad;
+
+mod logind;
+
 use clap::Parser;
-use std::path::Path;
-use std::process::Command;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitCode, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use gtk::gdk::{keys, EventKey, Screen};
-use gtk::glib::{timeout_add_local_once, Propagation};
+use gtk::gdk::{
+    keys, Cursor, CursorType, EventButton, EventKey, EventMask, ModifierType, Screen,
+    ScrollDirection, RGBA,
+};
+use gtk::gdk_pixbuf::{Colorspace, Pixbuf};
+use gtk::glib::{
+    self, child_watch_add_local, markup_escape_text, timeout_add_local, timeout_add_local_once,
+    ControlFlow, Pid, Propagation, SourceId,
+};
 use gtk::prelude::*;
-use gtk::{gio, Application, ApplicationWindow, CssProvider, Label, StyleContext};
+use gtk::{gio, Application, ApplicationWindow, CssProvider, DirectionType, Label, StyleContext};
 use gtk_layer_shell::LayerShell;
-use serde::Deserialize;
-use wleave::cli_opt::{Args, Protocol};
+use serde::{Deserialize, Serialize};
+use wleave::cli_opt::{
+    Args, ColorScheme, Edge, Ellipsize, FocusTarget, Geometry, IconPosition, KeybindStyle, Protocol,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct WButtonConfig {
     buttons: Vec<WButton>,
+    /// Separators, each as the number of buttons preceding it
+    separators: Vec<usize>,
+}
+
+/// An entry of the layout file, keeping plain button objects valid
+#[derive(Debug)]
+enum LayoutEntry {
+    Button(Box<WButton>),
+    Separator {
+        order: i32,
+        unknown_fields: Vec<String>,
+    },
+}
+
+impl LayoutEntry {
+    fn order(&self) -> i32 {
+        match self {
+            LayoutEntry::Button(button) => button.order,
+            LayoutEntry::Separator { order, .. } => *order,
+        }
+    }
+
+    /// The keys of the entry that are not fields of a button or separator
+    fn unknown_fields(&self) -> Vec<&str> {
+        match self {
+            LayoutEntry::Button(button) => {
+                button.unknown_fields.keys().map(String::as_str).collect()
+            }
+            LayoutEntry::Separator { unknown_fields, .. } => {
+                unknown_fields.iter().map(String::as_str).collect()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LayoutEntry {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Separator {
+            separator: bool,
+            #[serde(default)]
+            order: i32,
+            #[serde(flatten)]
+            unknown_fields: HashMap<String, serde_json::Value>,
+        }
+
+        let value = serde_json::Value::deserialize(deserializer)?;
+
+        if value.get("separator").is_some() {
+            let separator = Separator::deserialize(value).map_err(serde::de::Error::custom)?;
+            if !separator.separator {
+                return Err(serde::de::Error::custom("separator must be true"));
+            }
+
+            return Ok(LayoutEntry::Separator {
+                order: separator.order,
+                unknown_fields: separator.unknown_fields.into_keys().collect(),
+            });
+        }
+
+        WButton::deserialize(value)
+            .map(|button| LayoutEntry::Button(Box::new(button)))
+            .map_err(serde::de::Error::custom)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct WButton {
     label: String,
-    action: String,
+    action: ButtonAction,
+    #[serde(rename = "action-right", alias = "action-secondary")]
+    action_right: Option<ButtonAction>,
+    #[serde(rename = "action-middle")]
+    action_middle: Option<ButtonAction>,
+    #[serde(rename = "action-long")]
+    action_long: Option<ButtonAction>,
+    #[serde(rename = "long-press-ms")]
+    long_press_ms: Option<u32>,
+    #[serde(rename = "sequence-mode", default)]
+    sequence_mode: SequenceMode,
     text: String,
     keybind: String,
-    #[serde(default = "default_justify")]
-    justify: String,
-    #[serde(default = "default_width")]
+    #[serde(default)]
+    justify: Justify,
+    /// Horizontal alignment of the text, not a size
+    #[serde(default = "default_width", deserialize_with = "deserialize_alignment")]
     width: f32,
-    #[serde(default = "default_height")]
+    /// Vertical alignment of the text, not a size
+    #[serde(default = "default_height", deserialize_with = "deserialize_alignment")]
     height: f32,
+    #[serde(rename = "width-px")]
+    width_px: Option<u32>,
+    #[serde(rename = "height-px")]
+    height_px: Option<u32>,
+    wrap: Option<bool>,
+    ellipsize: Option<Ellipsize>,
     #[serde(default = "default_circular")]
     circular: bool,
+    tooltip: Option<String>,
+    icon: Option<String>,
+    #[serde(rename = "icon-position")]
+    icon_position: Option<IconPosition>,
+    #[serde(rename = "icon-symbolic")]
+    icon_symbolic: Option<String>,
+    #[serde(rename = "icon-color")]
+    icon_color: Option<String>,
+    #[serde(rename = "hide-text")]
+    hide_text: Option<bool>,
+    #[serde(rename = "col-span", default = "default_span")]
+    col_span: u32,
+    #[serde(rename = "row-span", default = "default_span")]
+    row_span: u32,
+    #[serde(default)]
+    order: i32,
+    group: Option<String>,
+    #[serde(rename = "text-command")]
+    text_command: Option<String>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(rename = "enabled-command")]
+    enabled_command: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(rename = "hold-to-activate-ms")]
+    hold_to_activate_ms: Option<u32>,
+    #[serde(rename = "keybind-confirm")]
+    keybind_confirm: Option<bool>,
+    #[serde(rename = "countdown-s")]
+    countdown_s: Option<u32>,
+    #[serde(default)]
+    default: bool,
+    #[serde(rename = "keep-open", default)]
+    keep_open: bool,
+    #[serde(rename = "close-mode", default)]
+    close_mode: CloseMode,
+    /// Keys that match no field, most likely misspelled ones
+    #[serde(flatten, skip_serializing)]
+    unknown_fields: HashMap<String, serde_json::Value>,
 }
 
-fn default_justify() -> String {
-    String::from("center")
+impl WButton {
+    /// The action run when the button is clicked with the given mouse button
+    fn action(&self, click: Click) -> Option<&ButtonAction> {
+        match click {
+            Click::Primary => Some(&self.action),
+            Click::Secondary => self.action_right.as_ref(),
+            Click::Middle => self.action_middle.as_ref(),
+            Click::LongPress => self.action_long.as_ref(),
+        }
+    }
+
+    fn steps(&self, click: Click) -> &[Action] {
+        match self.action(click) {
+            Some(ButtonAction::Single(action)) => std::slice::from_ref(action),
+            Some(ButtonAction::Sequence { sequence }) => sequence,
+            None => &[],
+        }
+    }
+
+    /// The commands started as soon as the button is selected
+    fn immediate_steps(&self, click: Click) -> &[Action] {
+        let steps = self.steps(click);
+        match self.sequence_mode {
+            SequenceMode::Immediate => &steps[..steps.len().saturating_sub(1)],
+            SequenceMode::Sequential => &[],
+        }
+    }
+
+    /// The commands run one after another once the delay has elapsed
+    fn delayed_steps(&self, click: Click) -> &[Action] {
+        let steps = self.steps(click);
+        match self.sequence_mode {
+            SequenceMode::Immediate => &steps[steps.len().saturating_sub(1)..],
+            SequenceMode::Sequential => steps,
+        }
+    }
 }
 
 fn default_width() -> f32 {
@@ -45,297 +226,4251 @@ fn default_height() -> f32 {
     0.9
 }
 
-fn default_circular() -> bool {
-    false
-}
-
-#[derive(Debug)]
-struct AppConfig {
-    margin_left: i32,
-    margin_right: i32,
-    margin_top: i32,
-    margin_bottom: i32,
-    column_spacing: u32,
-    row_spacing: u32,
-    delay_ms: u32,
-    protocol: Protocol,
-    buttons_per_row: u32,
-    close_on_lost_focus: bool,
-    button_config: WButtonConfig,
-    show_keybinds: bool,
-}
+/// Rejects alignments outside of 0.0 to 1.0, which are most likely meant as sizes
+fn deserialize_alignment<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<f32, D::Error> {
+    let alignment = f32::deserialize(deserializer)?;
 
-fn load_file_search<S>(
-    given_file: Option<&impl AsRef<Path>>,
-    file_name: &impl AsRef<Path>,
-    load_func: impl Fn(&dyn AsRef<Path>) -> Result<Option<S>, String>,
-) -> Result<S, String> {
-    if let Some(given_file) = given_file {
-        return match load_func(&given_file) {
-            Ok(Some(config)) => Ok(config),
-            Ok(None) => Err(format!(
-                "Failed to load {}: File does not exist",
-                given_file.as_ref().display()
-            )),
-            Err(e) => Err(e),
-        };
+    if !(0.0..=1.0).contains(&alignment) {
+        return Err(serde::de::Error::custom(format!(
+            "width and height align the text between 0.0 and 1.0, got {alignment}; \
+             use width-px and height-px to size the button"
+        )));
     }
 
-    let user_config_dir = dirs::config_dir().unwrap_or_else(|| {
-        dirs::home_dir().map_or_else(|| Path::new("~/.config").to_owned(), |p| p.join(".config"))
-    });
+    Ok(alignment)
+}
 
-    let user_css_path = user_config_dir.join("wleave");
-    let user_css_path_compat = user_config_dir.join("wlogout");
-
-    for path in &[
-        user_css_path.as_ref(),
-        user_css_path_compat.as_ref(),
-        Path::new("/etc/wleave"),
-        Path::new("/etc/wlogout"),
-        Path::new("/usr/local/etc/wleave"),
-        Path::new("/usr/local/etc/wlogout"),
-    ] {
-        let full_path = path.join(file_name);
-        if let Some(config) = load_func(&full_path)? {
-            eprintln!("File found in: {}", full_path.display());
-            return Ok(config);
-        } else {
-            eprintln!("No file found in: {}", full_path.display());
-        }
-    }
+fn default_circular() -> bool {
+    false
+}
 
-    Err(format!("No {} file found!", file_name.as_ref().display()))
+fn default_enabled() -> bool {
+    true
 }
 
-fn load_config_from_file(path: &dyn AsRef<Path>) -> Result<Option<WButtonConfig>, String> {
-    if !path.as_ref().is_file() {
-        return Ok(None);
-    }
+fn default_span() -> u32 {
+    1
+}
 
-    let file = std::fs::File::open(path)
-        .map_err(|e| format!("Failed to open file {}: {e}", path.as_ref().display()))?;
+/// When the menu closes relative to running the action
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum CloseMode {
+    #[default]
+    HideThenRun,
+    RunThenClose,
+    RunAndStay,
+}
 
-    let reader = std::io::BufReader::new(file);
+/// A command run either through `sh -c` or directly from an argument vector
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Action {
+    Shell(String),
+    Exec(Vec<String>),
+}
 
-    let mut buttons = Vec::new();
+/// The action of a button, either a single command or several run in order
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum ButtonAction {
+    Single(Action),
+    Sequence { sequence: Vec<Action> },
+}
 
-    let mut de = serde_json::Deserializer::from_reader(reader);
+/// How a button was activated, keys count as a primary click
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Click {
+    Primary,
+    Secondary,
+    Middle,
+    LongPress,
+}
 
-    loop {
-        match WButton::deserialize(&mut de) {
-            Ok(button) => buttons.push(button),
-            Err(e) if e.is_eof() => break Ok(Some(WButtonConfig { buttons })),
-            Err(e) => break Err(format!("Parsing failed: {e}")),
+impl Click {
+    fn from_mouse_button(button: u32) -> Option<Self> {
+        match button {
+            1 => Some(Click::Primary),
+            2 => Some(Click::Middle),
+            3 => Some(Click::Secondary),
+            _ => None,
         }
     }
 }
 
-fn load_config(file: Option<&impl AsRef<Path>>) -> Result<WButtonConfig, String> {
-    load_file_search(file, &"layout", load_config_from_file)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum SequenceMode {
+    /// Every command runs after the delay, each once the previous one succeeded
+    #[default]
+    Sequential,
+    /// The commands before the last one start right away, without waiting for each other
+    Immediate,
 }
 
-fn load_css_from_file(path: &dyn AsRef<Path>) -> Result<Option<CssProvider>, String> {
-    if !path.as_ref().is_file() {
-        return Ok(None);
+impl Action {
+    /// The command as written in the layout, for display
+    fn display(&self) -> String {
+        match self {
+            Action::Shell(command) => command.clone(),
+            Action::Exec(argv) => argv.join(" "),
+        }
     }
 
-    let provider = CssProvider::new();
-    provider
-        .load_from_file(&gio::File::for_path(path))
-        .map_err(|e| format!("Failed to load CSS: {e}"))?;
-    Ok(Some(provider))
+    fn command(&self) -> Command {
+        match self {
+            Action::Shell(command) => {
+                let mut cmd = Command::new("sh");
+                cmd.args(["-c", command]);
+                cmd
+            }
+            Action::Exec(argv) => {
+                let mut cmd = Command::new(&argv[0]);
+                cmd.args(&argv[1..]);
+                cmd
+            }
+        }
+    }
 }
 
-fn load_css(file: Option<&impl AsRef<Path>>) -> Result<CssProvider, String> {
-    load_file_search(file, &"style.css", load_css_from_file)
+/// A button widget along with the grid cells it occupies
+#[derive(Debug)]
+struct GridButton {
+    button: gtk::Button,
+    hold: Option<Rc<HoldActivation>>,
+    countdown_label: Option<Label>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
 }
 
-fn run_command(command: &str) {
-    if let Err(e) = Command::new("sh").args(["-c", command]).spawn() {
-        eprintln!("Execution error: {e}");
-    }
+/// The interactive state of the button grid
+#[derive(Debug)]
+struct Menu {
+    buttons: Vec<GridButton>,
+    /// The button waiting for its keybind to be pressed a second time
+    armed: RefCell<Option<(usize, SourceId)>>,
+    countdown: RefCell<Option<Countdown>>,
+    /// The overlay listing every keybind
+    help: gtk::Grid,
+    /// The text typed in search mode, shown in `search_label`
+    filter: RefCell<String>,
+    search_label: Label,
+    /// Whether `g` was pressed once, waiting for the second `g` of `gg`
+    pending_g: Cell<bool>,
+    /// The hardware keycodes of the keys held down, to tell key repeats from new presses
+    held_keys: RefCell<HashSet<u16>>,
+    /// Whether an action closing the menu was started, which ignores any further one
+    activated: Cell<bool>,
+    /// Kept here since GTK 3 gestures are not owned by their widget
+    #[allow(dead_code)]
+    swipe: Option<gtk::GestureDrag>,
 }
 
-fn on_option(command: &str, delay_ms: u32, window: ApplicationWindow) {
-    let state_inner = (command.to_owned(), window.clone());
-    window.connect_hide(move |_| {
-        let state_timer = state_inner.clone();
-        timeout_add_local_once(Duration::from_millis(delay_ms.into()), move || {
-            let (ref action, ref window_handle) = state_timer;
-            run_command(action);
-            window_handle.close();
-        });
-    });
-    window.hide();
+/// A countdown running before the action of a button is executed
+#[derive(Debug)]
+struct Countdown {
+    index: usize,
+    click: Click,
+    remaining: Cell<u32>,
+    /// The timer ticking every second, unset while paused
+    timer: RefCell<Option<SourceId>>,
+    config: Arc<AppConfig>,
+    window: ApplicationWindow,
 }
 
-fn handle_key(config: &Arc<AppConfig>, window: &ApplicationWindow, e: &EventKey) -> Propagation {
-    match e.keyval() {
-        keys::constants::Escape => {
-            window.close();
-        }
-        other => {
-            let key = other
-                .to_unicode()
-                .map(|c| c.to_string())
-                .or_else(|| other.name().map(|s| s.to_string()));
+impl Menu {
+    fn is_armed(&self, index: usize) -> bool {
+        matches!(*self.armed.borrow(), Some((armed, _)) if armed == index)
+    }
 
-            if let Some(ref key_name) = key {
-                let button = config
-                    .button_config
-                    .buttons
-                    .iter()
-                    .find(|b| b.keybind == *key_name);
+    fn arm(self: &Rc<Self>, index: usize, timeout: Duration) {
+        self.disarm();
 
-                if let Some(WButton { action, .. }) = button {
-                    let state_action = action.clone();
-                    on_option(&state_action, config.delay_ms, window.clone());
+        let button = &self.buttons[index].button;
+        button.style_context().add_class("armed");
+        button.grab_focus();
+
+        let menu = Rc::downgrade(self);
+        let timer = timeout_add_local_once(timeout, move || {
+            if let Some(menu) = menu.upgrade() {
+                if let Some((index, _)) = menu.armed.take() {
+                    menu.buttons[index]
+                        .button
+                        .style_context()
+                        .remove_class("armed");
                 }
             }
+        });
+
+        self.armed.replace(Some((index, timer)));
+    }
+
+    fn disarm(&self) {
+        if let Some((index, timer)) = self.armed.take() {
+            timer.remove();
+            self.buttons[index]
+                .button
+                .style_context()
+                .remove_class("armed");
         }
     }
 
-    Propagation::Proceed
-}
+    /// Runs the action of a button unless another one is already on its way
+    fn dispatch(
+        &self,
+        config: &Arc<AppConfig>,
+        index: usize,
+        click: Click,
+        window: ApplicationWindow,
+    ) {
+        if self.activated.get() {
+            return;
+        }
 
-fn app_main(config: &Arc<AppConfig>, app: &Application) {
-    let window = ApplicationWindow::builder()
-        .application(app)
-        .title("wleave")
-        .build();
+        // The menu stays usable while actions that keep it open run
+        if config.button_config.buttons[index].close_mode != CloseMode::RunAndStay {
+            self.activated.set(true);
+        }
 
-    match config.protocol {
-        Protocol::LayerShell => {
-            window.init_layer_shell();
-            window.set_layer(gtk_layer_shell::Layer::Overlay);
-            window.set_namespace("wleave");
-            window.set_exclusive_zone(-1);
-            window.set_keyboard_interactivity(true);
+        on_option(config, index, click, window);
+    }
+
+    /// Fades out and disables the buttons whose text and label do not contain the filter
+    fn apply_filter(&self, config: &AppConfig) {
+        let filter = self.filter.borrow().to_lowercase();
+
+        for (b, bttn) in self.buttons.iter().zip(&config.button_config.buttons) {
+            let matches = plain_text(&bttn.text).to_lowercase().contains(&filter)
+                || bttn.label.to_lowercase().contains(&filter);
 
-            window.set_anchor(gtk_layer_shell::Edge::Left, true);
-            window.set_anchor(gtk_layer_shell::Edge::Right, true);
-            window.set_anchor(gtk_layer_shell::Edge::Top, true);
-            window.set_anchor(gtk_layer_shell::Edge::Bottom, true);
+            b.button.set_sensitive(bttn.enabled && matches);
+            if matches {
+                b.button.style_context().remove_class("filtered-out");
+            } else {
+                b.button.style_context().add_class("filtered-out");
+            }
         }
-        Protocol::Xdg => {
-            window.fullscreen();
+
+        self.search_label.set_text(&self.filter.borrow());
+        self.search_label.set_visible(!filter.is_empty());
+
+        // Keep the focus on a button that can still be activated
+        if !self.buttons.iter().any(|b| b.button.is_focus()) {
+            if let Some(b) = self.buttons.iter().find(|b| b.button.is_sensitive()) {
+                b.button.grab_focus();
+            }
         }
     }
 
-    if config.close_on_lost_focus {
-        window.connect_focus_out_event(|window, _| {
-            if window.is_visible() {
-                window.close();
-            }
+    /// Clears the search filter, returning whether there was one
+    fn clear_filter(&self, config: &AppConfig) -> bool {
+        if self.filter.borrow().is_empty() {
+            return false;
+        }
 
-            Propagation::Proceed
-        });
+        self.filter.borrow_mut().clear();
+        self.apply_filter(config);
+        true
     }
 
-    let cfg = config.clone();
-    window.connect_key_press_event(move |window, e| handle_key(&cfg, window, e));
+    fn is_counting_down(&self) -> bool {
+        self.countdown.borrow().is_some()
+    }
 
-    let grid = gtk::Grid::new();
+    fn start_countdown(
+        self: &Rc<Self>,
+        config: &Arc<AppConfig>,
+        index: usize,
+        click: Click,
+        seconds: u32,
+        window: &ApplicationWindow,
+    ) {
+        if self.is_counting_down() {
+            return;
+        }
 
-    window.add(&grid);
+        let b = &self.buttons[index];
+        b.button.style_context().add_class("counting-down");
+        if let Some(label) = &b.countdown_label {
+            label.set_text(&seconds.to_string());
+            label.show();
+        }
 
-    grid.set_column_spacing(config.column_spacing);
-    grid.set_row_spacing(config.row_spacing);
-    grid.set_margin_top(config.margin_top);
-    grid.set_margin_bottom(config.margin_bottom);
-    grid.set_margin_start(config.margin_left);
-    grid.set_margin_end(config.margin_right);
-
-    for (i, bttn) in config.button_config.buttons.iter().enumerate() {
-        let label = if config.show_keybinds {
-            format!("{} [{}]", bttn.text, bttn.keybind)
-        } else {
-            bttn.text.to_owned()
-        };
+        self.countdown.replace(Some(Countdown {
+            index,
+            click,
+            remaining: Cell::new(seconds),
+            timer: RefCell::new(None),
+            config: config.clone(),
+            window: window.clone(),
+        }));
+
+        self.resume_countdown();
+    }
 
-        let justify = match bttn.justify.as_str() {
-            "center" => gtk::Justification::Center,
-            "fill" => gtk::Justification::Fill,
-            "left" => gtk::Justification::Left,
-            "right" => gtk::Justification::Right,
-            _ => gtk::Justification::Center,
+    fn resume_countdown(self: &Rc<Self>) {
+        let countdown = self.countdown.borrow();
+        let Some(countdown) = countdown.as_ref() else {
+            return;
         };
 
-        let button = gtk::Button::builder()
-            .label(&label)
-            .name(&bttn.label)
-            .hexpand(true)
-            .vexpand(true)
-            .build();
+        if countdown.timer.borrow().is_some() {
+            return;
+        }
 
-        if let Some(label) = button.child() {
-            if let Some(label) = label.downcast_ref::<Label>() {
-                label.set_xalign(bttn.width);
-                label.set_yalign(bttn.height);
-                label.set_use_markup(true);
-                label.set_justify(justify);
+        let menu = Rc::downgrade(self);
+        let timer = timeout_add_local(Duration::from_secs(1), move || match menu.upgrade() {
+            Some(menu) => menu.countdown_tick(),
+            None => ControlFlow::Break,
+        });
+
+        countdown.timer.replace(Some(timer));
+    }
+
+    fn pause_countdown(&self) {
+        if let Some(countdown) = self.countdown.borrow().as_ref() {
+            if let Some(timer) = countdown.timer.take() {
+                timer.remove();
             }
         }
+    }
 
-        if bttn.circular {
-            button.style_context().add_class("circular");
+    /// Stops the countdown without running the action, returns whether one was running
+    fn cancel_countdown(&self) -> bool {
+        let Some(countdown) = self.countdown.take() else {
+            return false;
+        };
+
+        if let Some(timer) = countdown.timer.take() {
+            timer.remove();
         }
 
-        let window_handle = window.clone();
-        let delay_ms = config.delay_ms;
-        let state_action = bttn.action.clone();
-        button.connect_clicked(move |_| on_option(&state_action, delay_ms, window_handle.clone()));
+        self.reset_countdown(countdown.index);
+        true
+    }
+
+    fn countdown_tick(&self) -> ControlFlow {
+        if let Some(countdown) = self.countdown.borrow().as_ref() {
+            let remaining = countdown.remaining.get().saturating_sub(1);
+            countdown.remaining.set(remaining);
+
+            if remaining > 0 {
+                if let Some(label) = &self.buttons[countdown.index].countdown_label {
+                    label.set_text(&remaining.to_string());
+                }
 
-        let x = i as u32 % config.buttons_per_row;
-        let y = i as u32 / config.buttons_per_row;
+                return ControlFlow::Continue;
+            }
+        }
+
+        // The timer source is removed by returning `Break`
+        if let Some(countdown) = self.countdown.take() {
+            self.reset_countdown(countdown.index);
+            self.dispatch(
+                &countdown.config,
+                countdown.index,
+                countdown.click,
+                countdown.window,
+            );
+        }
 
-        grid.attach(&button, x as i32, y as i32, 1, 1);
+        ControlFlow::Break
     }
 
-    window.show_all();
+    fn reset_countdown(&self, index: usize) {
+        let b = &self.buttons[index];
+        b.button.style_context().remove_class("counting-down");
+        if let Some(label) = &b.countdown_label {
+            label.hide();
+        }
+    }
 }
 
-fn main() {
-    let args = Args::parse();
+/// A press-and-hold activation, only completing once the button
+/// has been held down for the whole duration
+#[derive(Debug)]
+struct HoldActivation {
+    duration: Duration,
+    progress: gtk::ProgressBar,
+    timer: RefCell<Option<SourceId>>,
+}
 
-    let button_config = match load_config(args.layout.as_ref()) {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            eprintln!("Failed to load config: {e}");
+impl HoldActivation {
+    fn start(self: &Rc<Self>, button: &gtk::Button, on_complete: impl FnOnce() + 'static) {
+        if self.timer.borrow().is_some() {
             return;
         }
-    };
 
-    let config = Arc::new(AppConfig {
-        margin_top: args.margin_top.unwrap_or(args.margin),
-        margin_bottom: args.margin_bottom.unwrap_or(args.margin),
-        margin_left: args.margin_left.unwrap_or(args.margin),
-        margin_right: args.margin_right.unwrap_or(args.margin),
-        row_spacing: args.row_spacing,
-        column_spacing: args.column_spacing,
-        protocol: args.protocol,
-        buttons_per_row: args.buttons_per_row,
-        close_on_lost_focus: args.close_on_lost_focus,
-        show_keybinds: args.show_keybinds,
-        button_config,
-        delay_ms: args.delay_command_ms,
-    });
+        button.style_context().add_class("holding");
+        self.progress.set_fraction(0.0);
+        self.progress.show();
 
-    let app = Application::builder()
-        .application_id("sh.natty.Wleave")
-        .build();
+        let started = Instant::now();
+        let hold = Rc::downgrade(self);
+        let button = button.clone();
+        let mut on_complete = Some(on_complete);
+        let timer = timeout_add_local(Duration::from_millis(16), move || {
+            let Some(hold) = hold.upgrade() else {
+                return ControlFlow::Break;
+            };
 
-    app.connect_startup(move |_| match load_css(args.css.as_ref()) {
-        Ok(css) => StyleContext::add_provider_for_screen(
-            &Screen::default().expect("Could not connect to a display."),
-            &css,
-            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
-        ),
-        Err(e) => eprintln!("Failed to load CSS: {e}"),
-    });
+            let fraction = started.elapsed().as_secs_f64() / hold.duration.as_secs_f64();
+            if fraction < 1.0 {
+                hold.progress.set_fraction(fraction);
+                return ControlFlow::Continue;
+            }
+
+            hold.timer.take();
+            hold.reset(&button);
+            if let Some(on_complete) = on_complete.take() {
+                on_complete();
+            }
+
+            ControlFlow::Break
+        });
+
+        self.timer.replace(Some(timer));
+    }
+
+    fn cancel(&self, button: &gtk::Button) {
+        if let Some(timer) = self.timer.take() {
+            timer.remove();
+            self.reset(button);
+        }
+    }
+
+    fn reset(&self, button: &gtk::Button) {
+        button.style_context().remove_class("holding");
+        self.progress.hide();
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Justify {
+    #[default]
+    Center,
+    Fill,
+    Left,
+    Right,
+}
+
+/// Accepts the justifications in any case
+impl<'de> Deserialize<'de> for Justify {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        const VARIANTS: &[&str] = &["center", "fill", "left", "right"];
+
+        let justify = String::deserialize(deserializer)?;
+        match justify.to_lowercase().as_str() {
+            "center" => Ok(Justify::Center),
+            "fill" => Ok(Justify::Fill),
+            "left" => Ok(Justify::Left),
+            "right" => Ok(Justify::Right),
+            _ => Err(serde::de::Error::unknown_variant(&justify, VARIANTS)),
+        }
+    }
+}
+
+impl From<Justify> for gtk::Justification {
+    fn from(justify: Justify) -> Self {
+        match justify {
+            Justify::Center => gtk::Justification::Center,
+            Justify::Fill => gtk::Justification::Fill,
+            Justify::Left => gtk::Justification::Left,
+            Justify::Right => gtk::Justification::Right,
+        }
+    }
+}
+
+const DEFAULT_MARGIN: i32 = 230;
+
+/// Exit code when the menu was closed without selecting a button
+const EXIT_CANCELLED: u8 = 1;
+/// Exit code when the configuration could not be loaded
+const EXIT_CONFIG_ERROR: u8 = 2;
 
-    app.connect_activate(move |app| app_main(&config, app));
+/// Whether a button was selected, reported through the exit code
+static SELECTED: AtomicBool = AtomicBool::new(false);
 
-    app.run_with_args(&[] as &[&str]);
+#[derive(Debug, Copy, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Margin {
+    Pixels(i32),
+    Percent(f64),
+}
+
+impl Margin {
+    /// Picks the most specific margin, pixel values winning over percentages
+    fn select(
+        pixels: Option<i32>,
+        percent: Option<f64>,
+        global_pixels: Option<i32>,
+        global_percent: Option<f64>,
+    ) -> Self {
+        match (pixels, percent, global_pixels, global_percent) {
+            (Some(pixels), ..) => Margin::Pixels(pixels),
+            (None, Some(percent), ..) => Margin::Percent(percent),
+            (None, None, Some(pixels), _) => Margin::Pixels(pixels),
+            (None, None, None, Some(percent)) => Margin::Percent(percent),
+            (None, None, None, None) => Margin::Pixels(DEFAULT_MARGIN),
+        }
+    }
+
+    fn resolve(self, monitor_size: i32) -> i32 {
+        match self {
+            Margin::Pixels(pixels) => pixels,
+            Margin::Percent(percent) => (f64::from(monitor_size) * percent / 100.0).round() as i32,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct AppConfig {
+    margin_left: Margin,
+    margin_right: Margin,
+    margin_top: Margin,
+    margin_bottom: Margin,
+    column_spacing: u32,
+    row_spacing: u32,
+    #[serde(rename = "delay-command-ms")]
+    delay_ms: u32,
+    protocol: Protocol,
+    geometry: Option<Geometry>,
+    window_opacity: f64,
+    timeout_s: Option<u32>,
+    timeout_action: Option<String>,
+    timeout_ms: Option<u32>,
+    inhibit_idle: bool,
+    animation_ms: u32,
+    reduced_motion: bool,
+    output: Option<String>,
+    all_outputs: bool,
+    anchor: Vec<Edge>,
+    exclusive_zone: i32,
+    buttons_per_row: u32,
+    min_button_width: Option<u32>,
+    close_on_lost_focus: bool,
+    close_on_right_click: bool,
+    close_on_click_away: bool,
+    #[serde(rename = "layout")]
+    button_config: WButtonConfig,
+    show_keybinds: bool,
+    keybind_style: KeybindStyle,
+    keybind_format: String,
+    wrap: bool,
+    ellipsize: Ellipsize,
+    keybind_confirm: bool,
+    #[serde(
+        rename = "keybind-confirm-timeout-ms",
+        serialize_with = "serialize_millis"
+    )]
+    keybind_confirm_timeout: Duration,
+    scroll_nav: bool,
+    swipe_close_distance: Option<u32>,
+    ordinal_keybinds: bool,
+    focus_follows_hover: bool,
+    vim_keys: bool,
+    search: bool,
+    keybinds_by_position: bool,
+    keybinds_ignore_case: bool,
+    cursor: Option<String>,
+    help_key: String,
+    focus_default: Option<FocusTarget>,
+    cancel_keys: Vec<String>,
+    icon_size: i32,
+    icon_position: IconPosition,
+    icon_only: bool,
+    no_icons: bool,
+    pre_action: Option<Action>,
+    wait_pre_action: bool,
+    post_action: Option<Action>,
+    notify_command: Option<Action>,
+}
+
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+/// The directories in the user's configuration, searched before the system-wide ones
+fn user_config_search_dirs() -> Vec<PathBuf> {
+    let user_config_dir = dirs::config_dir().unwrap_or_else(|| {
+        dirs::home_dir().map_or_else(|| Path::new("~/.config").to_owned(), |p| p.join(".config"))
+    });
+
+    vec![
+        user_config_dir.join("wleave"),
+        user_config_dir.join("wlogout"),
+    ]
+}
+
+fn config_search_dirs() -> Vec<PathBuf> {
+    let mut dirs = user_config_search_dirs();
+    dirs.extend([
+        PathBuf::from("/etc/wleave"),
+        PathBuf::from("/etc/wlogout"),
+        PathBuf::from("/usr/local/etc/wleave"),
+        PathBuf::from("/usr/local/etc/wlogout"),
+    ]);
+    dirs
+}
+
+fn load_file_search<S>(
+    given_file: Option<&impl AsRef<Path>>,
+    file_name: &impl AsRef<Path>,
+    search_dirs: Vec<PathBuf>,
+    load_func: impl Fn(&dyn AsRef<Path>) -> Result<Option<S>, String>,
+) -> Result<S, String> {
+    if let Some(given_file) = given_file {
+        return match load_func(&given_file) {
+            Ok(Some(config)) => Ok(config),
+            Ok(None) => Err(format!(
+                "Failed to load {}: File does not exist",
+                given_file.as_ref().display()
+            )),
+            Err(e) => Err(e),
+        };
+    }
+
+    for path in search_dirs {
+        let full_path = path.join(file_name);
+        if let Some(config) = load_func(&full_path)? {
+            log!("File found in: {}", full_path.display());
+            return Ok(config);
+        } else {
+            log!("No file found in: {}", full_path.display());
+        }
+    }
+
+    Err(format!("No {} file found!", file_name.as_ref().display()))
+}
+
+/// Expands `$VAR` and `${VAR}` references to environment variables,
+/// unset variables expand to an empty string like in the shell.
+fn expand_env_vars(value: &str) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        let name = if chars.next_if_eq(&'{').is_some() {
+            Some(chars.by_ref().take_while(|&c| c != '}').collect::<String>())
+        } else {
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+            (!name.is_empty()).then_some(name)
+        };
+
+        match name {
+            Some(name) => expanded.push_str(&std::env::var(name).unwrap_or_default()),
+            None => expanded.push('$'),
+        }
+    }
+
+    expanded
+}
+
+/// Resolves an included layout file relative to the including file,
+/// falling back to the configuration search path.
+fn resolve_include(including_file: &Path, include: &Path) -> Result<PathBuf, String> {
+    if include.is_absolute() {
+        return Ok(include.to_owned());
+    }
+
+    let relative = including_file
+        .parent()
+        .map_or_else(|| include.to_owned(), |dir| dir.join(include));
+
+    if relative.is_file() {
+        return Ok(relative);
+    }
+
+    config_search_dirs()
+        .into_iter()
+        .map(|dir| dir.join(include))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            format!(
+                "Failed to resolve {} included from {}",
+                include.display(),
+                including_file.display()
+            )
+        })
+}
+
+fn load_layout_entries(
+    path: &Path,
+    strict: bool,
+    include_stack: &mut Vec<PathBuf>,
+    layout: &mut Vec<LayoutEntry>,
+) -> Result<(), String> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| format!("Failed to open file {}: {e}", path.display()))?;
+
+    if include_stack.contains(&canonical) {
+        return Err(format!("Include cycle detected at {}", path.display()));
+    }
+
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open file {}: {e}", path.display()))?;
+
+    let reader = std::io::BufReader::new(file);
+
+    include_stack.push(canonical);
+    parse_layout_entries(reader, path, strict, include_stack, layout)?;
+    include_stack.pop();
+
+    Ok(())
+}
+
+/// Resolves a relative icon path against the directory of the layout file declaring
+/// it, keeping it relative to the working directory when no such file exists there
+fn resolve_icon(layout_file: &Path, icon: &mut String) {
+    if decode_inline_icon(icon).is_some() || !is_icon_path(icon) || Path::new(icon).is_absolute() {
+        return;
+    }
+
+    let Some(dir) = layout_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    else {
+        return;
+    };
+
+    let path = dir.join(&*icon);
+    if path.is_file() {
+        *icon = path.to_string_lossy().into_owned();
+    }
+}
+
+/// Parses the entries of a layout, `path` names it in errors and is the base of relative includes.
+/// Unknown fields are reported as warnings, or as errors when `strict` is set.
+fn parse_layout_entries(
+    reader: impl Read,
+    path: &Path,
+    strict: bool,
+    include_stack: &mut Vec<PathBuf>,
+    layout: &mut Vec<LayoutEntry>,
+) -> Result<(), String> {
+    let entries = serde_json::Deserializer::from_reader(reader).into_iter::<serde_json::Value>();
+
+    for (i, entry) in entries.enumerate() {
+        let entry = entry.map_err(|e| format!("Parsing failed in {}: {e}", path.display()))?;
+
+        let Some(include) = entry.get("include") else {
+            let mut entry = LayoutEntry::deserialize(entry).map_err(|e| {
+                format!(
+                    "Parsing failed at entry {} of {}: {e}",
+                    i + 1,
+                    path.display()
+                )
+            })?;
+
+            let unknown_fields = entry.unknown_fields();
+            if !unknown_fields.is_empty() {
+                let message = format!(
+                    "Unknown {} {} at entry {} of {}",
+                    if unknown_fields.len() == 1 {
+                        "field"
+                    } else {
+                        "fields"
+                    },
+                    unknown_fields.join(", "),
+                    i + 1,
+                    path.display()
+                );
+
+                if strict {
+                    return Err(message);
+                }
+                log!("Warning: {message}");
+            }
+
+            if let LayoutEntry::Button(button) = &mut entry {
+                for icon in button.icon.iter_mut().chain(&mut button.icon_symbolic) {
+                    resolve_icon(path, icon);
+                }
+            }
+
+            if let LayoutEntry::Button(button) = &entry {
+                let clicks = [
+                    Click::Primary,
+                    Click::Secondary,
+                    Click::Middle,
+                    Click::LongPress,
+                ];
+                let actions = clicks
+                    .into_iter()
+                    .filter(|&click| button.action(click).is_some());
+
+                let error = actions.map(|click| button.steps(click)).find_map(|steps| {
+                    if steps.is_empty() {
+                        Some("the action sequence must not be empty")
+                    } else if steps
+                        .iter()
+                        .any(|step| matches!(step, Action::Exec(argv) if argv.is_empty()))
+                    {
+                        Some("the action must not be an empty list")
+                    } else {
+                        None
+                    }
+                });
+
+                if let Some(error) = error {
+                    return Err(format!(
+                        "Parsing failed at entry {} of {}: {error}",
+                        i + 1,
+                        path.display()
+                    ));
+                }
+            }
+
+            layout.push(entry);
+            continue;
+        };
+
+        let includes = match include {
+            serde_json::Value::String(file) => vec![PathBuf::from(file)],
+            serde_json::Value::Array(files) => files
+                .iter()
+                .filter_map(|file| file.as_str().map(PathBuf::from))
+                .collect(),
+            _ => {
+                return Err(format!(
+                    "Parsing failed at entry {} of {}: include must be a path or a list of paths",
+                    i + 1,
+                    path.display()
+                ))
+            }
+        };
+
+        for include in includes {
+            let include_path = resolve_include(path, &include)?;
+            log!("Including layout file: {}", include_path.display());
+            load_layout_entries(&include_path, strict, include_stack, layout)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn load_config_from_file(
+    path: &dyn AsRef<Path>,
+    strict: bool,
+) -> Result<Option<WButtonConfig>, String> {
+    if !path.as_ref().is_file() {
+        return Ok(None);
+    }
+
+    let mut layout = Vec::new();
+    load_layout_entries(path.as_ref(), strict, &mut Vec::new(), &mut layout)?;
+
+    build_button_config(layout).map(Some)
+}
+
+/// Parses the output of a shell command as the layout, includes in it are
+/// resolved against the working directory
+fn load_config_from_command(command: &str, strict: bool) -> Result<WButtonConfig, String> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(|e| format!("Failed to run the layout command: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!("The layout command failed: {}", output.status));
+    }
+
+    let mut layout = Vec::new();
+    parse_layout_entries(
+        output.stdout.as_slice(),
+        Path::new("layout-command"),
+        strict,
+        &mut Vec::new(),
+        &mut layout,
+    )?;
+
+    build_button_config(layout)
+}
+
+fn build_button_config(mut layout: Vec<LayoutEntry>) -> Result<WButtonConfig, String> {
+    // Stable, so entries without an explicit order keep their declaration order
+    layout.sort_by_key(LayoutEntry::order);
+
+    let mut buttons = Vec::new();
+    let mut separators = Vec::new();
+    for entry in layout {
+        match entry {
+            LayoutEntry::Button(mut button) => {
+                for value in button.env.values_mut() {
+                    *value = expand_env_vars(value);
+                }
+
+                if button.keep_open {
+                    button.close_mode = CloseMode::RunAndStay;
+                }
+
+                buttons.push(*button);
+            }
+            LayoutEntry::Separator { .. } => separators.push(buttons.len()),
+        }
+    }
+
+    // An empty menu could only be dismissed, most likely the layout is malformed
+    if buttons.is_empty() {
+        return Err("The layout contains no buttons".to_owned());
+    }
+
+    for button in &buttons {
+        if let Some(color) = &button.icon_color {
+            if RGBA::parse(color).is_err() {
+                return Err(format!(
+                    "Invalid icon-color {color} of button {}",
+                    button.label
+                ));
+            }
+        }
+    }
+
+    let mut defaults = buttons.iter().filter(|b| b.default);
+    if let (Some(first), Some(second)) = (defaults.next(), defaults.next()) {
+        return Err(format!(
+            "Buttons {} and {} are both marked as default",
+            first.label, second.label
+        ));
+    }
+
+    let mut labels = HashMap::new();
+    for (i, button) in buttons.iter().enumerate() {
+        if let Some(first) = labels.insert(&button.label, i) {
+            log!(
+                "Warning: buttons {} and {} share the label {}",
+                first + 1,
+                i + 1,
+                button.label
+            );
+        }
+    }
+
+    Ok(WButtonConfig {
+        buttons,
+        separators,
+    })
+}
+
+fn load_config(file: Option<&impl AsRef<Path>>, strict: bool) -> Result<WButtonConfig, String> {
+    load_file_search(file, &"layout", config_search_dirs(), |path| {
+        load_config_from_file(path, strict)
+    })
+}
+
+#[derive(Debug, Copy, Clone)]
+enum StartupCommand {
+    Text,
+    Enabled,
+}
+
+fn read_command_output(child: &mut Child) -> Result<String, String> {
+    let mut output = String::new();
+
+    child
+        .wait()
+        .map_err(|e| e.to_string())
+        .and_then(|status| match child.stdout.take() {
+            _ if !status.success() => Err(status.to_string()),
+            Some(mut stdout) => stdout
+                .read_to_string(&mut output)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            None => Ok(()),
+        })?;
+
+    Ok(output)
+}
+
+fn finish_startup_command(
+    button: &mut WButton,
+    kind: StartupCommand,
+    result: Result<String, String>,
+) {
+    match kind {
+        StartupCommand::Text => {
+            let output = result.unwrap_or_else(|e| {
+                log!("Warning: the text command of {} failed: {e}", button.label);
+                String::new()
+            });
+
+            button.text = button.text.replace("{output}", output.trim());
+        }
+        StartupCommand::Enabled => {
+            if let Err(e) = result {
+                log!(
+                    "Disabling {}: its enabled command failed: {e}",
+                    button.label
+                );
+                button.enabled = false;
+            }
+        }
+    }
+}
+
+/// Runs the `text-command` and `enabled-command` of every button concurrently,
+/// giving up on commands that did not finish within `timeout`.
+fn run_startup_commands(buttons: &mut [WButton], timeout: Duration) {
+    let jobs = buttons
+        .iter()
+        .enumerate()
+        .flat_map(|(i, button)| {
+            [
+                (i, StartupCommand::Text, button.text_command.clone()),
+                (i, StartupCommand::Enabled, button.enabled_command.clone()),
+            ]
+        })
+        .filter_map(|(i, kind, command)| Some((i, kind, command?)))
+        .collect::<Vec<_>>();
+
+    let mut children = Vec::with_capacity(jobs.len());
+
+    for (i, kind, command) in jobs {
+        match Command::new("sh")
+            .args(["-c", &command])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => children.push((i, kind, child)),
+            Err(e) => finish_startup_command(&mut buttons[i], kind, Err(e.to_string())),
+        }
+    }
+
+    let deadline = Instant::now() + timeout;
+
+    while !children.is_empty() {
+        children.retain_mut(|(i, kind, child)| match child.try_wait() {
+            Ok(None) => true,
+            Ok(Some(_)) | Err(_) => {
+                finish_startup_command(&mut buttons[*i], *kind, read_command_output(child));
+                false
+            }
+        });
+
+        if Instant::now() >= deadline {
+            for (i, kind, child) in &mut children {
+                let _ = child.kill();
+                let _ = child.wait();
+                let error = format!("timed out after {} ms", timeout.as_millis());
+                finish_startup_command(&mut buttons[*i], *kind, Err(error));
+            }
+            break;
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn load_css_from_file(path: &dyn AsRef<Path>) -> Result<Option<CssProvider>, String> {
+    if !path.as_ref().is_file() {
+        return Ok(None);
+    }
+
+    let provider = CssProvider::new();
+    provider
+        .load_from_file(&gio::File::for_path(path))
+        .map_err(|e| format!("Failed to load CSS: {e}"))?;
+    Ok(Some(provider))
+}
+
+/// Loads the given stylesheet or the first style.css found, only looking in the
+/// user's configuration with `user_only`
+fn load_css(file: Option<&impl AsRef<Path>>, user_only: bool) -> Result<CssProvider, String> {
+    let search_dirs = if user_only {
+        user_config_search_dirs()
+    } else {
+        config_search_dirs()
+    };
+
+    load_file_search(file, &"style.css", search_dirs, load_css_from_file)
+}
+
+fn run_command(action: &Action, env: &HashMap<String, String>) -> Option<Child> {
+    match action.command().envs(env).spawn() {
+        Ok(child) => Some(child),
+        Err(e) => {
+            log!("Execution error: {e}");
+            None
+        }
+    }
+}
+
+/// Starts the pre-action and calls `then` right away, or once it exited with
+/// --wait-pre-action, without blocking the main loop in the meantime
+fn run_pre_action(
+    config: &AppConfig,
+    env: &HashMap<String, String>,
+    then: impl FnOnce() + 'static,
+) {
+    let Some(child) = config.pre_action.as_ref().and_then(|a| run_command(a, env)) else {
+        then();
+        return;
+    };
+
+    if config.wait_pre_action {
+        let mut then = Some(then);
+        child_watch_add_local(Pid(child.id() as i32), move |_, _| {
+            if let Some(then) = then.take() {
+                then();
+            }
+        });
+    } else {
+        // Only reaps the pre-action
+        child_watch_add_local(Pid(child.id() as i32), |_, _| {});
+        then();
+    }
+}
+
+fn on_option(config: &Arc<AppConfig>, index: usize, click: Click, window: ApplicationWindow) {
+    SELECTED.store(true, Ordering::Relaxed);
+
+    #[cfg(feature = "dbus")]
+    if let Some(app) = window.application() {
+        dbus::emit_selected(&app, &config.button_config.buttons[index].label);
+    }
+
+    let button = &config.button_config.buttons[index];
+    for step in button.immediate_steps(click) {
+        if let Some(child) = run_command(step, &button.env) {
+            notify_spawned(config, button, &child);
+
+            // Only reaps the command, the next one does not wait for it
+            child_watch_add_local(Pid(child.id() as i32), |_, _| {});
+        }
+    }
+
+    match button.close_mode {
+        CloseMode::HideThenRun => hide_then_run(config, index, click, window),
+        CloseMode::RunThenClose => run_then_close(config, index, click, window),
+        CloseMode::RunAndStay => run_in_place(config, index, click, &window),
+    }
+}
+
+/// Tells the notify command about a command of an action that was started,
+/// without waiting for it
+fn notify_spawned(config: &AppConfig, button: &WButton, child: &Child) {
+    let Some(notify_command) = &config.notify_command else {
+        return;
+    };
+
+    let mut env = button.env.clone();
+    env.insert("WLEAVE_PID".to_owned(), child.id().to_string());
+    env.insert("WLEAVE_LABEL".to_owned(), button.label.clone());
+
+    if let Some(notify) = run_command(notify_command, &env) {
+        child_watch_add_local(Pid(notify.id() as i32), |_, _| {});
+    }
+}
+
+/// Runs the action of a button, followed by the post-action once it exits
+fn run_action(config: &Arc<AppConfig>, index: usize, click: Click, window: &ApplicationWindow) {
+    run_step(config, index, click, 0, window.application());
+}
+
+/// Runs a command of the action, then the next one if it succeeded,
+/// and the post-action after the last one
+fn run_step(
+    config: &Arc<AppConfig>,
+    index: usize,
+    click: Click,
+    step: usize,
+    app: Option<Application>,
+) {
+    let button = &config.button_config.buttons[index];
+    let steps = button.delayed_steps(click);
+    let Some(child) = run_command(&steps[step], &button.env) else {
+        return;
+    };
+    notify_spawned(config, button, &child);
+
+    // Keep the application alive while there is something left to run
+    let last = step + 1 == steps.len();
+    let mut hold = (!last || config.post_action.is_some())
+        .then(|| app.as_ref().map(|app| app.hold()))
+        .flatten();
+
+    let config = config.clone();
+    child_watch_add_local(Pid(child.id() as i32), move |_, status| {
+        let button = &config.button_config.buttons[index];
+
+        if !last {
+            if status == 0 {
+                run_step(&config, index, click, step + 1, app.clone());
+            } else {
+                log!(
+                    "Command {} of the action of {} failed, skipping the rest",
+                    step + 1,
+                    button.label
+                );
+            }
+        } else if let Some(post_action) = &config.post_action {
+            if let Some(child) = run_command(post_action, &button.env) {
+                child_watch_add_local(Pid(child.id() as i32), |_, _| {});
+            }
+        }
+
+        hold.take();
+    });
+}
+
+/// The delay before running an action, which leaves time for the window to
+/// fade out and is skipped when animations are disabled in the GTK settings
+fn action_delay(config: &AppConfig) -> Duration {
+    let animations = gtk::Settings::default().is_none_or(|s| s.is_gtk_enable_animations());
+
+    if animations {
+        Duration::from_millis(config.delay_ms.into())
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// Runs the action right away while the menu stays open
+fn run_in_place(config: &Arc<AppConfig>, index: usize, click: Click, window: &ApplicationWindow) {
+    run_action(config, index, click, window);
+    window.present();
+}
+
+/// Runs the pre-action and the action first, closing the menu only after the delay,
+/// so that actions such as screen lockers can take over the screen before it goes away
+fn run_then_close(config: &Arc<AppConfig>, index: usize, click: Click, window: ApplicationWindow) {
+    let cfg = config.clone();
+    run_pre_action(
+        config,
+        &config.button_config.buttons[index].env,
+        move || {
+            run_action(&cfg, index, click, &window);
+
+            timeout_add_local_once(action_delay(&cfg), move || {
+                window.close();
+            });
+        },
+    );
+}
+
+/// Hides the menu, then runs the pre-action, the action and the post-action
+fn hide_then_run(config: &Arc<AppConfig>, index: usize, click: Click, window: ApplicationWindow) {
+    let delay = action_delay(config);
+
+    // When animated, the menu fades out during the delay and is hidden afterwards
+    let fade_out = animation_duration(config).is_some() && !delay.is_zero();
+    let after_hide = if fade_out { Duration::ZERO } else { delay };
+
+    let state_inner = (config.clone(), window.clone());
+    window.connect_hide(move |_| {
+        let (ref config, _) = state_inner;
+        let state_timer = state_inner.clone();
+        run_pre_action(
+            config,
+            &config.button_config.buttons[index].env,
+            move || {
+                timeout_add_local_once(after_hide, move || {
+                    let (ref config, ref window_handle) = state_timer;
+                    run_action(config, index, click, window_handle);
+                    window_handle.close();
+                });
+            },
+        );
+    });
+
+    match window.child() {
+        Some(contents) if fade_out => {
+            fade(&contents, contents.opacity(), 0.0, delay, move || {
+                window.hide()
+            });
+        }
+        _ => window.hide(),
+    }
+}
+
+/// Distance between two intervals on the same axis, zero when they overlap
+fn interval_distance(a: u32, a_len: u32, b: u32, b_len: u32) -> u32 {
+    if a + a_len <= b {
+        b - (a + a_len) + 1
+    } else if b + b_len <= a {
+        a - (b + b_len) + 1
+    } else {
+        0
+    }
+}
+
+fn focus_neighbor(buttons: &[GridButton], direction: DirectionType) {
+    let Some(current) = buttons.iter().find(|b| b.button.is_focus()) else {
+        if let Some(first) = buttons.first() {
+            first.button.grab_focus();
+        }
+        return;
+    };
+
+    // Pick the closest button in the given direction, preferring the ones
+    // that share a row or column with the focused button
+    let target = buttons
+        .iter()
+        .filter(|b| b.button.is_sensitive())
+        .filter_map(|b| {
+            let (gap, offset) = match direction {
+                DirectionType::Left if b.x + b.width <= current.x => (
+                    current.x - (b.x + b.width),
+                    interval_distance(b.y, b.height, current.y, current.height),
+                ),
+                DirectionType::Right if b.x >= current.x + current.width => (
+                    b.x - (current.x + current.width),
+                    interval_distance(b.y, b.height, current.y, current.height),
+                ),
+                DirectionType::Up if b.y + b.height <= current.y => (
+                    current.y - (b.y + b.height),
+                    interval_distance(b.x, b.width, current.x, current.width),
+                ),
+                DirectionType::Down if b.y >= current.y + current.height => (
+                    b.y - (current.y + current.height),
+                    interval_distance(b.x, b.width, current.x, current.width),
+                ),
+                _ => return None,
+            };
+
+            Some(((gap, offset), b))
+        })
+        .min_by_key(|(distance, _)| *distance);
+
+    if let Some((_, b)) = target {
+        b.button.grab_focus();
+    }
+}
+
+fn activate_button(
+    config: &Arc<AppConfig>,
+    menu: &Rc<Menu>,
+    index: usize,
+    click: Click,
+    window: &ApplicationWindow,
+) {
+    // Only a single countdown may run, other buttons wait until it is cancelled
+    if menu.is_counting_down() || menu.activated.get() {
+        return;
+    }
+
+    let b = &menu.buttons[index];
+    match &b.hold {
+        Some(hold) => {
+            let cfg = config.clone();
+            let menu_handle = menu.clone();
+            let window_handle = window.clone();
+            hold.start(&b.button, move || {
+                run_button(&cfg, &menu_handle, index, click, &window_handle)
+            });
+        }
+        None => run_button(config, menu, index, click, window),
+    }
+}
+
+/// Runs the action of a button, after its countdown if it has one
+fn run_button(
+    config: &Arc<AppConfig>,
+    menu: &Rc<Menu>,
+    index: usize,
+    click: Click,
+    window: &ApplicationWindow,
+) {
+    match config.button_config.buttons[index].countdown_s {
+        Some(seconds) if seconds > 0 => menu.start_countdown(config, index, click, seconds, window),
+        _ => menu.dispatch(config, index, click, window.clone()),
+    }
+}
+
+/// Edits the search filter with the typed text and Backspace, and activates
+/// the only match with Return, returns `None` for keys it leaves alone
+fn handle_search_key(
+    config: &Arc<AppConfig>,
+    window: &ApplicationWindow,
+    menu: &Rc<Menu>,
+    e: &EventKey,
+    repeat: bool,
+) -> Option<Propagation> {
+    let keyval = e.keyval();
+
+    match keyval {
+        keys::constants::BackSpace => {
+            menu.filter.borrow_mut().pop()?;
+        }
+        keys::constants::Return | keys::constants::KP_Enter => {
+            if menu.filter.borrow().is_empty() {
+                return None;
+            }
+
+            let mut matches = menu
+                .buttons
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.button.is_sensitive());
+            let (Some((index, _)), None) = (matches.next(), matches.next()) else {
+                return None;
+            };
+
+            if !repeat {
+                activate_button(config, menu, index, Click::Primary, window);
+            }
+            return Some(Propagation::Stop);
+        }
+        _ => {
+            let shortcut = e
+                .state()
+                .intersects(ModifierType::CONTROL_MASK | ModifierType::MOD1_MASK);
+            let c = keyval.to_unicode().filter(|c| !c.is_control())?;
+
+            // Space still activates the focused button until something was typed
+            if shortcut || (c == ' ' && menu.filter.borrow().is_empty()) {
+                return None;
+            }
+
+            menu.filter.borrow_mut().push(c);
+        }
+    }
+
+    menu.apply_filter(config);
+    Some(Propagation::Stop)
+}
+
+/// Translates a key event with the first keyboard layout that gives the key a
+/// Latin character, so that keybinds keep their position with other layouts
+fn latin_keyval(window: &ApplicationWindow, e: &EventKey) -> Option<keys::Key> {
+    let keymap = gtk::gdk::Keymap::for_display(&window.display())?;
+    let keycode = u32::from(e.hardware_keycode());
+
+    let mut groups: Vec<_> = keymap
+        .entries_for_keycode(keycode)
+        .iter()
+        .map(|(key, _)| key.group())
+        .collect();
+    groups.sort_unstable();
+    groups.dedup();
+
+    groups.into_iter().find_map(|group| {
+        let (keyval, ..) = keymap.translate_keyboard_state(keycode, e.state(), group)?;
+        let keyval = keys::Key::from(keyval);
+
+        keyval
+            .to_unicode()
+            .is_some_and(|c| c.is_ascii_graphic())
+            .then_some(keyval)
+    })
+}
+
+fn handle_key(
+    config: &Arc<AppConfig>,
+    window: &ApplicationWindow,
+    menu: &Rc<Menu>,
+    e: &EventKey,
+) -> Propagation {
+    let buttons = &menu.buttons;
+
+    let keyval = if config.keybinds_by_position {
+        latin_keyval(window, e).unwrap_or_else(|| e.keyval())
+    } else {
+        e.keyval()
+    };
+    let key = keyval
+        .to_unicode()
+        .map(|c| c.to_string())
+        .or_else(|| keyval.name().map(|s| s.to_string()));
+
+    // Holding a key repeats its presses, which must not activate or toggle anything twice
+    let repeat = !menu.held_keys.borrow_mut().insert(e.hardware_keycode());
+
+    let name = keyval.name();
+    let cancel = [key.as_deref(), name.as_deref()]
+        .into_iter()
+        .flatten()
+        .any(|key| is_cancel_key(config, key));
+
+    let direction = match keyval {
+        _ if cancel && repeat => return Propagation::Stop,
+        _ if cancel && config.search && menu.clear_filter(config) => return Propagation::Stop,
+        _ if cancel => {
+            return if dismiss(menu, window) {
+                Propagation::Proceed
+            } else {
+                Propagation::Stop
+            };
+        }
+        other => {
+            if let Some(ref key_name) = key {
+                // Typing goes to the search instead of the keybinds and the help key
+                if config.search {
+                    if let Some(propagation) = handle_search_key(config, window, menu, e, repeat) {
+                        return propagation;
+                    }
+                }
+
+                let button = config
+                    .button_config
+                    .buttons
+                    .iter()
+                    .position(|b| b.enabled && keybind_matches(config, &b.keybind, key_name))
+                    .or_else(|| ordinal_button(config, key_name));
+
+                if let Some(index) = button {
+                    if repeat {
+                        return Propagation::Stop;
+                    }
+
+                    let confirm = config.button_config.buttons[index]
+                        .keybind_confirm
+                        .unwrap_or(config.keybind_confirm);
+
+                    if confirm && !menu.is_armed(index) {
+                        window.set_focus_visible(true);
+                        menu.arm(index, config.keybind_confirm_timeout);
+                        return Propagation::Stop;
+                    }
+
+                    menu.disarm();
+                    activate_button(config, menu, index, Click::Primary, window);
+                    return Propagation::Proceed;
+                }
+
+                if *key_name == config.help_key {
+                    if !repeat {
+                        menu.help.set_visible(!menu.help.is_visible());
+                    }
+                    return Propagation::Stop;
+                }
+            }
+
+            if !e.is_modifier() {
+                menu.disarm();
+
+                if other != keys::constants::g {
+                    menu.pending_g.set(false);
+                }
+            }
+
+            match other {
+                keys::constants::Left | keys::constants::KP_Left => DirectionType::Left,
+                keys::constants::Right | keys::constants::KP_Right => DirectionType::Right,
+                keys::constants::Up | keys::constants::KP_Up => DirectionType::Up,
+                keys::constants::Down | keys::constants::KP_Down => DirectionType::Down,
+                keys::constants::h if config.vim_keys => DirectionType::Left,
+                keys::constants::l if config.vim_keys => DirectionType::Right,
+                keys::constants::k if config.vim_keys => DirectionType::Up,
+                keys::constants::j if config.vim_keys => DirectionType::Down,
+                keys::constants::g if config.vim_keys => {
+                    if menu.pending_g.replace(false) {
+                        window.set_focus_visible(true);
+                        focus_edge(buttons, false);
+                    } else {
+                        menu.pending_g.set(true);
+                    }
+                    return Propagation::Stop;
+                }
+                keys::constants::G if config.vim_keys => {
+                    window.set_focus_visible(true);
+                    focus_edge(buttons, true);
+                    return Propagation::Stop;
+                }
+                keys::constants::Tab | keys::constants::ISO_Left_Tab => {
+                    window.set_focus_visible(true);
+                    cycle_focus(buttons, if other == keys::constants::Tab { 1 } else { -1 });
+                    return Propagation::Stop;
+                }
+                keys::constants::Home | keys::constants::KP_Home => {
+                    window.set_focus_visible(true);
+                    focus_edge(buttons, false);
+                    return Propagation::Stop;
+                }
+                keys::constants::End | keys::constants::KP_End => {
+                    window.set_focus_visible(true);
+                    focus_edge(buttons, true);
+                    return Propagation::Stop;
+                }
+                keys::constants::Return | keys::constants::KP_Enter | keys::constants::space => {
+                    let focused = buttons.iter().position(|b| b.button.is_focus());
+                    let index = match other {
+                        keys::constants::space => focused,
+                        _ => focused.or_else(|| {
+                            config
+                                .button_config
+                                .buttons
+                                .iter()
+                                .position(|b| b.default && b.enabled)
+                        }),
+                    };
+
+                    if let Some(index) = index {
+                        if !repeat {
+                            activate_button(config, menu, index, Click::Primary, window);
+                        }
+                        return Propagation::Stop;
+                    }
+                    return Propagation::Proceed;
+                }
+                _ => return Propagation::Proceed,
+            }
+        }
+    };
+
+    move_focus(buttons, window, direction);
+    Propagation::Stop
+}
+
+/// Cancels the countdown or hides the help if either is shown, otherwise
+/// closes the menu, returning whether it was closed
+fn dismiss(menu: &Menu, window: &ApplicationWindow) -> bool {
+    if menu.cancel_countdown() {
+        return false;
+    }
+
+    if menu.help.is_visible() {
+        menu.help.hide();
+        return false;
+    }
+
+    window.close();
+    true
+}
+
+fn move_focus(buttons: &[GridButton], window: &ApplicationWindow, direction: DirectionType) {
+    // GtkGrid mirrors its columns for right-to-left locales, while the
+    // button positions are kept in left-to-right order
+    let direction = match (direction, window.direction()) {
+        (DirectionType::Left, gtk::TextDirection::Rtl) => DirectionType::Right,
+        (DirectionType::Right, gtk::TextDirection::Rtl) => DirectionType::Left,
+        (direction, _) => direction,
+    };
+
+    window.set_focus_visible(true);
+    focus_neighbor(buttons, direction);
+}
+
+/// Decodes icons embedded in the layout, either as a `data:` URI or as inline SVG markup.
+fn decode_inline_icon(icon: &str) -> Option<Result<Vec<u8>, String>> {
+    if icon.starts_with("<svg") {
+        return Some(Ok(icon.as_bytes().to_vec()));
+    }
+
+    let uri = icon.strip_prefix("data:")?;
+    let Some((header, data)) = uri.split_once(',') else {
+        return Some(Err("missing ',' in the data URI".to_owned()));
+    };
+
+    if header.ends_with(";base64") {
+        return Some(Ok(glib::base64_decode(data)));
+    }
+
+    Some(percent_decode(data).ok_or_else(|| "invalid escape sequence in the data URI".to_owned()))
+}
+
+fn percent_decode(data: &str) -> Option<Vec<u8>> {
+    let mut bytes = data.bytes();
+    let mut decoded = Vec::with_capacity(data.len());
+
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+
+    Some(decoded)
+}
+
+/// Whether an icon refers to an image file rather than to the icon theme
+fn is_icon_path(icon: &str) -> bool {
+    icon.contains('/')
+        || Path::new(icon)
+            .extension()
+            .is_some_and(|ext| matches!(ext.to_str(), Some("svg" | "svgz" | "png" | "xpm")))
+}
+
+/// Loads an icon either from a file path, from data embedded in the layout or,
+/// for bare names such as `system-shutdown-symbolic`, from the current icon theme.
+/// With a `color`, the symbolic variant of a theme icon is used when the theme has
+/// one, since only symbolic theme icons can be recolored, while image files are painted over.
+/// Images are rendered at `scale` times their size to stay sharp on HiDPI outputs.
+fn load_icon(icon: &str, size: i32, scale: i32, color: Option<&str>) -> Option<gtk::Image> {
+    let from_pixbuf = |pixbuf: Pixbuf| {
+        let pixbuf = match color {
+            Some(color) => recolor_pixbuf(&pixbuf, color),
+            None => pixbuf,
+        };
+        match pixbuf.create_surface(scale, None::<&gtk::gdk::Window>) {
+            Some(surface) => gtk::Image::from_surface(Some(&surface)),
+            None => gtk::Image::from_pixbuf(Some(&pixbuf)),
+        }
+    };
+    let pixels = size * scale;
+
+    if let Some(data) = decode_inline_icon(icon) {
+        let pixbuf = data.and_then(|data| {
+            let stream = gio::MemoryInputStream::from_bytes(&glib::Bytes::from_owned(data));
+            Pixbuf::from_stream_at_scale(&stream, pixels, pixels, true, gio::Cancellable::NONE)
+                .map_err(|e| e.to_string())
+        });
+
+        return match pixbuf {
+            Ok(pixbuf) => Some(from_pixbuf(pixbuf)),
+            Err(e) => {
+                log!("Failed to load an embedded icon: {e}");
+                None
+            }
+        };
+    }
+
+    if is_icon_path(icon) {
+        return match Pixbuf::from_file_at_scale(icon, pixels, pixels, true) {
+            Ok(pixbuf) => Some(from_pixbuf(pixbuf)),
+            Err(e) => {
+                log!("Failed to load icon {icon}: {e}");
+                None
+            }
+        };
+    }
+
+    let symbolic =
+        (color.is_some() && !icon.ends_with("-symbolic")).then(|| format!("{icon}-symbolic"));
+    let theme = gtk::IconTheme::default();
+    let name = symbolic
+        .iter()
+        .map(String::as_str)
+        .chain([icon])
+        .find(|name| theme.as_ref().is_some_and(|theme| theme.has_icon(name)));
+
+    match name {
+        Some(name) => {
+            let image = gtk::Image::from_icon_name(Some(name), gtk::IconSize::Dialog);
+            image.set_pixel_size(size);
+            Some(image)
+        }
+        None => {
+            log!("Icon {icon} not found in the icon theme");
+            None
+        }
+    }
+}
+
+/// Paints every pixel of an image in `color` while keeping its transparency,
+/// like GTK does for symbolic icons
+fn recolor_pixbuf(pixbuf: &Pixbuf, color: &str) -> Pixbuf {
+    let Ok(color) = RGBA::parse(color) else {
+        return pixbuf.clone();
+    };
+    let Ok(pixbuf) = pixbuf.add_alpha(false, 0, 0, 0) else {
+        return pixbuf.clone();
+    };
+
+    let channel = |value: f64| (value * 255.0).round() as u8;
+    let (red, green, blue) = (
+        channel(color.red()),
+        channel(color.green()),
+        channel(color.blue()),
+    );
+    let rowstride = pixbuf.rowstride() as usize;
+    let width = pixbuf.width() as usize;
+
+    let mut pixels = pixbuf.read_pixel_bytes().to_vec();
+    for row in pixels.chunks_mut(rowstride) {
+        for pixel in row[..width * 4].chunks_exact_mut(4) {
+            pixel[0] = red;
+            pixel[1] = green;
+            pixel[2] = blue;
+            pixel[3] = (f64::from(pixel[3]) * color.alpha()).round() as u8;
+        }
+    }
+
+    Pixbuf::from_mut_slice(
+        pixels,
+        Colorspace::Rgb,
+        true,
+        8,
+        pixbuf.width(),
+        pixbuf.height(),
+        pixbuf.rowstride(),
+    )
+}
+
+/// Appends the keybind to the tooltip of a button, unless it has none
+fn keybind_tooltip(tooltip: &str, keybind: &str) -> String {
+    if keybind.is_empty() {
+        tooltip.to_string()
+    } else {
+        format!("{tooltip} ({})", markup_escape_text(keybind))
+    }
+}
+
+/// Keeps long texts within the cell of the button by wrapping or ellipsizing them
+fn shorten_label(label: &Label, wrap: bool, ellipsize: Ellipsize) {
+    let mode = match ellipsize {
+        Ellipsize::None => gtk::pango::EllipsizeMode::None,
+        Ellipsize::Start => gtk::pango::EllipsizeMode::Start,
+        Ellipsize::Middle => gtk::pango::EllipsizeMode::Middle,
+        Ellipsize::End => gtk::pango::EllipsizeMode::End,
+    };
+
+    if wrap {
+        label.set_line_wrap(true);
+        label.set_line_wrap_mode(gtk::pango::WrapMode::WordChar);
+    }
+
+    label.set_ellipsize(mode);
+
+    // Request as little width as possible, the label still takes up the
+    // whole width of the cell it is given
+    if wrap || mode != gtk::pango::EllipsizeMode::None {
+        label.set_max_width_chars(1);
+    }
+}
+
+/// Puts the icon next to the text label of a button
+fn add_icon_content(
+    button: &gtk::Button,
+    image: gtk::Widget,
+    text: gtk::Widget,
+    position: IconPosition,
+) {
+    let orientation = match position {
+        IconPosition::Top | IconPosition::Bottom => {
+            image.set_vexpand(true);
+            gtk::Orientation::Vertical
+        }
+        IconPosition::Left | IconPosition::Right => {
+            image.set_hexpand(true);
+            image.set_valign(gtk::Align::Center);
+            gtk::Orientation::Horizontal
+        }
+    };
+
+    let content = gtk::Box::new(orientation, 0);
+    match position {
+        IconPosition::Top | IconPosition::Left => {
+            content.add(&image);
+            content.add(&text);
+        }
+        IconPosition::Bottom | IconPosition::Right => {
+            content.add(&text);
+            content.add(&image);
+        }
+    }
+    button.add(&content);
+}
+
+/// Shows the symbolic variant of an icon while the button is hovered or focused
+fn symbolic_icon_stack(
+    button: &gtk::Button,
+    image: gtk::Image,
+    symbolic: gtk::Image,
+) -> gtk::Stack {
+    let stack = gtk::Stack::new();
+    stack.add_named(&image, "icon");
+    stack.add_named(&symbolic, "symbolic");
+
+    let handle = stack.clone();
+    button.connect_state_flags_changed(move |button, _| {
+        let highlighted = button
+            .state_flags()
+            .intersects(gtk::StateFlags::PRELIGHT | gtk::StateFlags::FOCUSED);
+
+        handle.set_visible_child_name(if highlighted { "symbolic" } else { "icon" });
+    });
+
+    stack
+}
+
+/// Recolors a symbolic theme icon independently of the text color
+fn set_icon_color(image: &gtk::Image, color: &str) {
+    let Ok(color) = RGBA::parse(color) else {
+        return;
+    };
+
+    let provider = CssProvider::new();
+    match provider.load_from_data(format!("image {{ color: {color}; }}").as_bytes()) {
+        Ok(_) => image
+            .style_context()
+            .add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_USER),
+        Err(e) => log!("Failed to apply the icon color {color}: {e}"),
+    }
+}
+
+/// Builds the stylesheet for `--background-color` and `--background-opacity`,
+/// the opacity replacing the alpha of the color, which is black by default
+fn background_css(color: Option<&str>, opacity: Option<f64>) -> Result<Option<String>, String> {
+    if color.is_none() && opacity.is_none() {
+        return Ok(None);
+    }
+
+    let mut rgba = match color {
+        Some(color) => {
+            RGBA::parse(color).map_err(|_| format!("Invalid background color {color}"))?
+        }
+        None => RGBA::BLACK,
+    };
+
+    if let Some(opacity) = opacity {
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err(format!(
+                "The background opacity must be between 0.0 and 1.0, got {opacity}"
+            ));
+        }
+
+        rgba = RGBA::new(rgba.red(), rgba.green(), rgba.blue(), opacity);
+    }
+
+    Ok(Some(format!("window {{ background-color: {rgba}; }}")))
+}
+
+/// Checks that the button given by `--focus-default` exists
+fn check_focus_target(target: &FocusTarget, buttons: &[WButton]) -> Result<(), String> {
+    match target {
+        FocusTarget::Index(index) if *index > buttons.len() => Err(format!(
+            "Cannot focus button {index}, there are only {} buttons",
+            buttons.len()
+        )),
+        FocusTarget::Label(label) if !buttons.iter().any(|b| &b.label == label) => Err(format!(
+            "Cannot focus button {label}, no button has this label"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects buttons whose keybind would be taken by a cancel key, ignoring case like
+/// the keybinds do with --keybinds-ignore-case
+fn check_cancel_keys(
+    cancel_keys: &[String],
+    buttons: &[WButton],
+    ignore_case: bool,
+) -> Result<(), String> {
+    for button in buttons {
+        let is_cancel_key = |key: &String| {
+            if ignore_case {
+                key.to_lowercase() == button.keybind.to_lowercase()
+            } else {
+                *key == button.keybind
+            }
+        };
+
+        if cancel_keys.iter().any(is_cancel_key) {
+            return Err(format!(
+                "The keybind {} of button {} is also a cancel key",
+                button.keybind, button.label
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Cancel keys follow the same case rule as the keybinds they are checked against
+fn is_cancel_key(config: &AppConfig, key: &str) -> bool {
+    config
+        .cancel_keys
+        .iter()
+        .any(|cancel_key| keybind_matches(config, cancel_key, key))
+}
+
+fn keybind_matches(config: &AppConfig, keybind: &str, key: &str) -> bool {
+    if config.keybinds_ignore_case {
+        keybind.to_lowercase() == key.to_lowercase()
+    } else {
+        keybind == key
+    }
+}
+
+/// With --keybinds-ignore-case, keybinds differing only in case select the first of the buttons
+fn warn_case_conflicts(buttons: &[WButton]) {
+    for (i, button) in buttons.iter().enumerate() {
+        let keybind = button.keybind.to_lowercase();
+        let other = buttons[..i]
+            .iter()
+            .find(|b| b.keybind != button.keybind && b.keybind.to_lowercase() == keybind);
+
+        if let Some(other) = other {
+            log!(
+                "Warning: the keybinds {} of button {} and {} of button {} only differ in case, \
+                 pass --keybinds-ignore-case false to tell them apart",
+                other.keybind,
+                other.label,
+                button.keybind,
+                button.label
+            );
+        }
+    }
+}
+
+/// Button keybinds are matched first, so they shadow the navigation keys of --vim-keys
+fn warn_vim_key_conflicts(buttons: &[WButton], ignore_case: bool) {
+    for button in vim_key_conflicts(buttons, ignore_case) {
+        log!(
+            "Warning: the keybind {} of button {} takes precedence over the --vim-keys navigation",
+            button.keybind,
+            button.label
+        );
+    }
+}
+
+/// Button keybinds take precedence over the help key, which leaves no key to show the help
+fn warn_help_key_conflicts(buttons: &[WButton], help_key: &str, ignore_case: bool) {
+    let conflict = buttons.iter().find(|button| {
+        if ignore_case {
+            button.keybind.to_lowercase() == help_key.to_lowercase()
+        } else {
+            button.keybind == help_key
+        }
+    });
+
+    if let Some(button) = conflict {
+        log!(
+            "Warning: the keybind {} of button {} takes precedence over the --help-key",
+            button.keybind,
+            button.label
+        );
+    }
+}
+
+/// The buttons whose keybind is also a --vim-keys navigation key, such as `J`
+/// for `j` when keybinds ignore case
+fn vim_key_conflicts(buttons: &[WButton], ignore_case: bool) -> impl Iterator<Item = &WButton> {
+    const VIM_KEYS: [&str; 6] = ["h", "j", "k", "l", "g", "G"];
+
+    buttons.iter().filter(move |button| {
+        VIM_KEYS.iter().any(|key| {
+            if ignore_case {
+                key.eq_ignore_ascii_case(&button.keybind)
+            } else {
+                *key == button.keybind
+            }
+        })
+    })
+}
+
+/// With --ordinal-keybinds, the digits 1 to 9 select the buttons in display order,
+/// unless a button explicitly uses the digit as its keybind
+fn ordinal_button(config: &AppConfig, key: &str) -> Option<usize> {
+    if !config.ordinal_keybinds
+        || config
+            .button_config
+            .buttons
+            .iter()
+            .any(|b| b.keybind == key)
+    {
+        return None;
+    }
+
+    let index = key.parse::<usize>().ok().filter(|n| (1..=9).contains(n))? - 1;
+    config
+        .button_config
+        .buttons
+        .get(index)
+        .filter(|b| b.enabled)
+        .map(|_| index)
+}
+
+/// The keybind of a button as shown to the user, which is its ordinal
+/// with --ordinal-keybinds when it has no keybind of its own
+fn shown_keybind(config: &AppConfig, index: usize) -> String {
+    let keybind = &config.button_config.buttons[index].keybind;
+
+    if keybind.is_empty() && config.ordinal_keybinds && index < 9 {
+        (index + 1).to_string()
+    } else {
+        keybind.clone()
+    }
+}
+
+/// The keybind as shown on a button, following --keybind-format
+fn keybind_hint(config: &AppConfig, keybind: &str) -> String {
+    markup_escape_text(&config.keybind_format.replace("{keybind}", keybind)).to_string()
+}
+
+/// Builds the overlay listing the keybind and text of every button
+fn build_help(config: &AppConfig) -> gtk::Grid {
+    let help = gtk::Grid::builder()
+        .halign(gtk::Align::Center)
+        .valign(gtk::Align::Center)
+        .column_spacing(24)
+        .row_spacing(8)
+        .no_show_all(true)
+        .build();
+    help.style_context().add_class("help");
+
+    for (row, bttn) in config.button_config.buttons.iter().enumerate() {
+        let keybind = shown_keybind(config, row);
+        let key = Label::builder().label(&keybind).xalign(1.0).build();
+        key.style_context().add_class("help-keybind");
+
+        let text = Label::builder()
+            .label(&bttn.text)
+            .use_markup(true)
+            .xalign(0.0)
+            .build();
+
+        // The help itself stays hidden until toggled, its contents are shown right away
+        key.show();
+        text.show();
+        help.attach(&key, 0, row as i32, 1, 1);
+        help.attach(&text, 1, row as i32, 1, 1);
+    }
+
+    help
+}
+
+/// Focuses the first or the last button that can be selected
+fn focus_edge(buttons: &[GridButton], last: bool) {
+    let mut enabled = buttons.iter().filter(|b| b.button.is_sensitive());
+    let target = if last {
+        enabled.next_back()
+    } else {
+        enabled.next()
+    };
+
+    if let Some(b) = target {
+        b.button.grab_focus();
+    }
+}
+
+fn cycle_focus(buttons: &[GridButton], step: isize) {
+    if buttons.is_empty() {
+        return;
+    }
+
+    // Follow the grid row by row rather than the order of the layout file
+    let mut order: Vec<_> = buttons.iter().collect();
+    order.sort_by_key(|b| (b.y, b.x));
+
+    let len = order.len() as isize;
+    let mut next = match order.iter().position(|b| b.button.is_focus()) {
+        Some(i) => i as isize,
+        None if step > 0 => -1,
+        None => len,
+    };
+
+    // Skip over disabled buttons, which cannot take the focus
+    for _ in 0..len {
+        next = (next + step).rem_euclid(len);
+        let button = &order[next as usize].button;
+        if button.is_sensitive() {
+            button.grab_focus();
+            return;
+        }
+    }
+}
+
+fn window_monitor(window: &ApplicationWindow) -> Option<gtk::gdk::Monitor> {
+    let display = window.display();
+
+    window
+        .window()
+        .and_then(|w| display.monitor_at_window(&w))
+        .or_else(|| {
+            window
+                .is_layer_window()
+                .then(|| LayerShell::monitor(window))
+                .flatten()
+        })
+        .or_else(|| display.primary_monitor())
+        .or_else(|| display.monitor(0))
+}
+
+/// The text of Pango markup without its tags, or the markup itself when it is invalid
+fn plain_text(markup: &str) -> String {
+    match gtk::pango::parse_markup(markup, '\0') {
+        Ok((_, text, _)) => text.to_string(),
+        Err(_) => markup.to_owned(),
+    }
+}
+
+/// A human readable name of a monitor, GTK 3 does not know the names of the outputs
+fn monitor_name(monitor: &gtk::gdk::Monitor) -> String {
+    [monitor.manufacturer(), monitor.model()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds the monitor given by its number or a part of its manufacturer and model,
+/// warning about the available monitors when none matches
+fn find_output(display: &gtk::gdk::Display, output: &str) -> Option<(i32, gtk::gdk::Monitor)> {
+    let monitors: Vec<_> = (0..display.n_monitors())
+        .filter_map(|i| display.monitor(i).map(|monitor| (i, monitor)))
+        .collect();
+
+    let needle = output.to_lowercase();
+    let found = match output.parse::<i32>() {
+        Ok(number) => monitors.iter().find(|(i, _)| *i == number),
+        Err(_) => monitors
+            .iter()
+            .find(|(_, monitor)| monitor_name(monitor).to_lowercase().contains(&needle)),
+    };
+
+    if found.is_none() {
+        let available: Vec<_> = monitors
+            .iter()
+            .map(|(i, monitor)| format!("{i}: {}", monitor_name(monitor)))
+            .collect();
+
+        log!(
+            "Warning: no output matches {output}, using the default one. Available outputs: {}",
+            available.join(", ")
+        );
+    }
+
+    found.cloned()
+}
+
+/// Underlines the first occurrence of a single-character keybind in the markup text,
+/// ignoring case and leaving tags and entities alone
+fn underline_keybind(text: &str, keybind: &str) -> Option<String> {
+    let mut chars = keybind.chars();
+    let (Some(key), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+
+    let mut in_tag = false;
+    let mut in_entity = false;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            '&' if !in_tag => in_entity = true,
+            ';' if in_entity => in_entity = false,
+            _ if !in_tag && !in_entity && c.to_lowercase().eq(key.to_lowercase()) => {
+                let end = i + c.len_utf8();
+                return Some(format!(
+                    "{}<u>{}</u>{}",
+                    &text[..i],
+                    &text[i..end],
+                    &text[end..]
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The first row below everything placed so far
+fn free_row(occupied: &HashSet<(u32, u32)>, next_cell: u32, per_row: u32) -> u32 {
+    occupied
+        .iter()
+        .map(|&(_, y)| y + 1)
+        .max()
+        .unwrap_or(0)
+        .max(next_cell.div_ceil(per_row))
+}
+
+/// Puts a separator on its own row below everything placed so far,
+/// returning the first cell after it
+fn attach_separator(
+    grid: &gtk::Grid,
+    occupied: &HashSet<(u32, u32)>,
+    next_cell: u32,
+    per_row: u32,
+) -> u32 {
+    let row = free_row(occupied, next_cell, per_row);
+
+    let separator = gtk::Separator::new(gtk::Orientation::Horizontal);
+    grid.attach(&separator, 0, row as i32, per_row as i32, 1);
+
+    (row + 1) * per_row
+}
+
+/// Sets the cursor of the window, keeping the default one when the name is unknown
+fn apply_cursor(window: &ApplicationWindow, name: &str) {
+    let Some(gdk_window) = window.window() else {
+        return;
+    };
+
+    let display = gdk_window.display();
+    let cursor = match name {
+        "none" => Cursor::for_display(&display, CursorType::BlankCursor),
+        name => Cursor::from_name(&display, name),
+    };
+
+    if cursor.is_none() {
+        log!("Cursor {name} not found, using the default cursor");
+    }
+
+    gdk_window.set_cursor(cursor.as_ref());
+}
+
+fn apply_margins(config: &AppConfig, window: &ApplicationWindow, grid: &gtk::Grid) {
+    let (width, height) = window_monitor(window)
+        .map(|monitor| {
+            let geometry = monitor.geometry();
+            (geometry.width(), geometry.height())
+        })
+        .unwrap_or_default();
+
+    grid.set_margin_top(config.margin_top.resolve(height));
+    grid.set_margin_bottom(config.margin_bottom.resolve(height));
+    grid.set_margin_start(config.margin_left.resolve(width));
+    grid.set_margin_end(config.margin_right.resolve(width));
+}
+
+/// Returns the number of buttons per row, reduced to what fits the window
+/// when a minimum button width is set
+fn columns(config: &AppConfig, window: &ApplicationWindow) -> u32 {
+    let Some(min_width) = config.min_button_width else {
+        return config.buttons_per_row;
+    };
+
+    let available = match config.geometry {
+        Some(Geometry { width, .. }) => width,
+        None => window_monitor(window).map_or(0, |monitor| monitor.geometry().width()),
+    };
+
+    let available =
+        available - config.margin_left.resolve(available) - config.margin_right.resolve(available);
+
+    // Every column but the last is followed by the column spacing
+    let fitting = (available.max(0) as u32 + config.column_spacing)
+        / (min_width + config.column_spacing).max(1);
+
+    fitting.clamp(1, config.buttons_per_row.max(1))
+}
+
+/// Runs `on_long_press` once the primary button has been held down on the button
+/// for the given time, or else the long press time of the GTK settings
+fn connect_long_press(button: &gtk::Button, ms: Option<u32>, on_long_press: impl Fn() + 'static) {
+    let ms =
+        ms.unwrap_or_else(|| gtk::Settings::default().map_or(500, |s| s.gtk_long_press_time()));
+    let delay = Duration::from_millis(ms.into());
+
+    let timer = Rc::new(RefCell::new(None::<SourceId>));
+    let on_long_press = Rc::new(on_long_press);
+
+    let press_timer = timer.clone();
+    button.connect_button_press_event(move |_, e| {
+        if Click::from_mouse_button(e.button()) == Some(Click::Primary) {
+            let timer = press_timer.clone();
+            let on_long_press = on_long_press.clone();
+            let source = timeout_add_local_once(delay, move || {
+                timer.take();
+                on_long_press();
+            });
+
+            if let Some(previous) = press_timer.replace(Some(source)) {
+                previous.remove();
+            }
+        }
+
+        Propagation::Proceed
+    });
+
+    let release_timer = timer.clone();
+    button.connect_button_release_event(move |_, _| {
+        if let Some(source) = release_timer.take() {
+            source.remove();
+        }
+        Propagation::Proceed
+    });
+
+    button.connect_leave_notify_event(move |_, _| {
+        if let Some(source) = timer.take() {
+            source.remove();
+        }
+        Propagation::Proceed
+    });
+}
+
+/// Covers every output but the one showing the menu with an empty layer surface,
+/// following hotplugged outputs until the menu is hidden
+fn add_backdrops(config: &AppConfig, window: &ApplicationWindow, menu_monitor: gtk::gdk::Monitor) {
+    let Some(app) = window.application() else {
+        return;
+    };
+
+    let backdrops = Rc::new(RefCell::new(Vec::new()));
+    let exclusive_zone = config.exclusive_zone;
+
+    let add_backdrop = {
+        let backdrops = backdrops.clone();
+        Rc::new(move |monitor: &gtk::gdk::Monitor| {
+            let backdrop = ApplicationWindow::builder()
+                .application(&app)
+                .title("wleave")
+                .build();
+            backdrop.style_context().add_class("backdrop");
+
+            backdrop.init_layer_shell();
+            backdrop.set_layer(gtk_layer_shell::Layer::Overlay);
+            backdrop.set_namespace("wleave");
+            backdrop.set_exclusive_zone(exclusive_zone);
+            backdrop.set_monitor(monitor);
+
+            for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+                backdrop.set_anchor(layer_shell_edge(edge), true);
+            }
+
+            backdrop.show_all();
+            backdrops.borrow_mut().push((monitor.clone(), backdrop));
+        })
+    };
+
+    let display = window.display();
+    for monitor in (0..display.n_monitors()).filter_map(|i| display.monitor(i)) {
+        if monitor != menu_monitor {
+            add_backdrop(&monitor);
+        }
+    }
+
+    let added = display.connect_monitor_added(move |_, monitor| add_backdrop(monitor));
+
+    let removed_backdrops = backdrops.clone();
+    let removed = display.connect_monitor_removed(move |_, monitor| {
+        removed_backdrops
+            .borrow_mut()
+            .retain(|(backdrop_monitor, backdrop)| {
+                let keep = backdrop_monitor != monitor;
+                if !keep {
+                    backdrop.close();
+                }
+                keep
+            });
+    });
+
+    // Hiding the menu is the first step of running an action, while closing
+    // it right away may only destroy it
+    let handlers = RefCell::new(Some((added, removed)));
+    let remove_backdrops = Rc::new(move |window: &ApplicationWindow| {
+        if let Some((added, removed)) = handlers.take() {
+            window.display().disconnect(added);
+            window.display().disconnect(removed);
+        }
+
+        for (_, backdrop) in backdrops.take() {
+            backdrop.close();
+        }
+    });
+
+    let on_hide = remove_backdrops.clone();
+    window.connect_hide(move |window| on_hide(window));
+    window.connect_destroy(move |window| remove_backdrops(window));
+}
+
+/// How long the menu fades in, or None when it is not animated at all, also
+/// when animations are turned off with --reduced-motion or in the GTK settings
+fn animation_duration(config: &AppConfig) -> Option<Duration> {
+    let enabled = gtk::Settings::default().is_none_or(|s| s.is_gtk_enable_animations());
+
+    (config.animation_ms > 0 && enabled && !config.reduced_motion)
+        .then(|| Duration::from_millis(config.animation_ms.into()))
+}
+
+/// Animates the opacity of the widget from `from` to `to` on its frame clock,
+/// calling `done` once it reached `to`. The animation stops without calling
+/// `done` when another one takes over the opacity.
+fn fade(
+    widget: &impl IsA<gtk::Widget>,
+    from: f64,
+    to: f64,
+    duration: Duration,
+    done: impl FnOnce() + 'static,
+) {
+    let start = Cell::new(None);
+    let done = RefCell::new(Some(done));
+    let last = Cell::new(from);
+
+    widget.set_opacity(from);
+    widget.add_tick_callback(move |widget, clock| {
+        // GTK keeps the opacity as 8 bits, so it does not come back exactly
+        if (widget.opacity() - last.get()).abs() > 0.01 {
+            return ControlFlow::Break;
+        }
+
+        let now = clock.frame_time();
+        let started = start.get().unwrap_or(now);
+        start.set(Some(started));
+
+        let progress = ((now - started) as f64 / duration.as_micros() as f64).min(1.0);
+        last.set(from + (to - from) * progress);
+        widget.set_opacity(last.get());
+
+        if progress < 1.0 {
+            return ControlFlow::Continue;
+        }
+
+        if let Some(done) = done.take() {
+            done();
+        }
+        ControlFlow::Break
+    });
+}
+
+/// Keeps the session from going idle while the window is mapped, so that an
+/// idle screen locker does not start underneath the menu
+fn inhibit_idle(app: &Application, window: &ApplicationWindow) {
+    let cookie = Rc::new(Cell::new(None));
+
+    let map_app = app.clone();
+    let map_cookie = cookie.clone();
+    window.connect_map(move |window| {
+        let reason = Some("Showing the logout menu");
+        match map_app.inhibit(Some(window), gtk::ApplicationInhibitFlags::IDLE, reason) {
+            0 => log!("Failed to inhibit idle while the menu is shown"),
+            inhibited => map_cookie.set(Some(inhibited)),
+        }
+    });
+
+    let app = app.clone();
+    window.connect_unmap(move |_| {
+        if let Some(inhibited) = cookie.take() {
+            app.uninhibit(inhibited);
+        }
+    });
+}
+
+/// The countdown of --timeout-s, started over by any input
+struct IdleCountdown {
+    seconds: u32,
+    remaining: Cell<u32>,
+    /// Shows the remaining seconds
+    show: Box<dyn Fn(u32)>,
+}
+
+impl IdleCountdown {
+    fn new(seconds: u32, show: impl Fn(u32) + 'static) -> Self {
+        show(seconds);
+        IdleCountdown {
+            seconds,
+            remaining: Cell::new(seconds),
+            show: Box::new(show),
+        }
+    }
+
+    fn reset(&self) {
+        self.remaining.set(self.seconds);
+        (self.show)(self.seconds);
+    }
+
+    /// Counts down a second, returns whether the time is up
+    fn tick(&self) -> bool {
+        let left = self.remaining.get().saturating_sub(1);
+        self.remaining.set(left);
+        if left > 0 {
+            (self.show)(left);
+        }
+        left == 0
+    }
+}
+
+/// Starts the idle countdown over before handling a key, since the key
+/// handler stops most keys from reaching any later handler
+fn reset_then_handle(
+    idle: Option<&IdleCountdown>,
+    handle: impl FnOnce() -> Propagation,
+) -> Propagation {
+    if let Some(idle) = idle {
+        idle.reset();
+    }
+    handle()
+}
+
+/// Closes the menu, or runs the button of `--timeout-action`, once no key was
+/// pressed and the pointer did not move for `seconds`, showing the remaining
+/// time in a label over the menu. Key presses have to reset the returned
+/// countdown through `reset_then_handle`.
+fn close_when_idle(
+    config: &Arc<AppConfig>,
+    menu: &Rc<Menu>,
+    window: &ApplicationWindow,
+    overlay: &gtk::Overlay,
+    seconds: u32,
+) -> Rc<IdleCountdown> {
+    let action = config.timeout_action.as_ref().and_then(|label| {
+        let buttons = &config.button_config.buttons;
+        buttons.iter().position(|b| &b.label == label)
+    });
+    let describe = match action {
+        Some(index) => {
+            let text = plain_text(&config.button_config.buttons[index].text);
+            Rc::new(move |left: u32| format!("{text} in {left}s")) as Rc<dyn Fn(u32) -> String>
+        }
+        None => Rc::new(|left: u32| format!("Closing in {left}s")),
+    };
+
+    let label = Label::builder()
+        .halign(gtk::Align::End)
+        .valign(gtk::Align::End)
+        .build();
+    label.style_context().add_class("auto-close");
+    overlay.add_overlay(&label);
+
+    let show_label = label.clone();
+    let idle = Rc::new(IdleCountdown::new(seconds, move |left| {
+        show_label.set_text(&describe(left))
+    }));
+
+    let motion_idle = idle.clone();
+    window.add_events(EventMask::POINTER_MOTION_MASK);
+    window.connect_motion_notify_event(move |_, _| {
+        motion_idle.reset();
+        Propagation::Proceed
+    });
+
+    let config = config.clone();
+    let menu = Rc::downgrade(menu);
+    let window = window.clone();
+    let timer_idle = idle.clone();
+    timeout_add_local(Duration::from_secs(1), move || {
+        let Some(menu) = menu.upgrade() else {
+            return ControlFlow::Break;
+        };
+
+        // A selected button closes the menu on its own, and a countdown has
+        // its own way of being cancelled
+        if menu.activated.get() || !window.is_visible() {
+            return ControlFlow::Break;
+        }
+        if menu.is_counting_down() {
+            return ControlFlow::Continue;
+        }
+
+        if timer_idle.tick() {
+            label.hide();
+            match action {
+                // Like a keybind, but without holding it down
+                Some(index) if menu.buttons[index].button.is_sensitive() => {
+                    run_button(&config, &menu, index, Click::Primary, &window)
+                }
+                _ => window.close(),
+            }
+            return ControlFlow::Break;
+        }
+
+        ControlFlow::Continue
+    });
+
+    idle
+}
+
+/// Lets a downward touch swipe of at least `distance` pixels close the menu,
+/// moving the buttons along while dragging
+fn swipe_to_close(
+    config: &Arc<AppConfig>,
+    window: &ApplicationWindow,
+    grid: &gtk::Grid,
+    distance: u32,
+) -> gtk::GestureDrag {
+    // Movement allowed before the swipe takes over, so that taps reach the buttons
+    const SLOP: f64 = 16.0;
+
+    let drag = gtk::GestureDrag::new(window);
+    drag.set_touch_only(true);
+    drag.set_propagation_phase(gtk::PropagationPhase::Capture);
+
+    let claimed = Rc::new(Cell::new(false));
+    let margins = Rc::new(Cell::new((0, 0)));
+
+    let begin_claimed = claimed.clone();
+    let begin_margins = margins.clone();
+    let begin_grid = grid.clone();
+    drag.connect_drag_begin(move |_, _, _| {
+        begin_claimed.set(false);
+        begin_margins.set((begin_grid.margin_top(), begin_grid.margin_bottom()));
+    });
+
+    let update_claimed = claimed.clone();
+    let update_grid = grid.clone();
+    drag.connect_drag_update(move |drag, dx, dy| {
+        if !update_claimed.get() {
+            if dy < SLOP || dy < dx.abs() {
+                return;
+            }
+
+            update_claimed.set(true);
+            drag.set_state(gtk::EventSequenceState::Claimed);
+        }
+
+        let (top, bottom) = margins.get();
+        let offset = dy.max(0.0) as i32;
+        update_grid.set_margin_top(top + offset);
+        update_grid.set_margin_bottom((bottom - offset).max(0));
+    });
+
+    let cfg = config.clone();
+    let end_grid = grid.clone();
+    let window_handle = window.clone();
+    drag.connect_drag_end(move |_, _, dy| {
+        if !claimed.get() {
+            return;
+        }
+
+        if dy >= f64::from(distance) {
+            window_handle.close();
+        } else {
+            apply_margins(&cfg, &window_handle, &end_grid);
+        }
+    });
+
+    drag
+}
+
+fn layer_shell_edge(edge: Edge) -> gtk_layer_shell::Edge {
+    match edge {
+        Edge::Left => gtk_layer_shell::Edge::Left,
+        Edge::Right => gtk_layer_shell::Edge::Right,
+        Edge::Top => gtk_layer_shell::Edge::Top,
+        Edge::Bottom => gtk_layer_shell::Edge::Bottom,
+    }
+}
+
+/// Picks the button focused when the menu opens, by default the button marked
+/// as default or else the first enabled one
+fn initial_focus(config: &AppConfig, buttons: &[GridButton]) -> Option<gtk::Button> {
+    let sensitive = |b: &&GridButton| b.button.is_sensitive();
+
+    let mut order: Vec<_> = buttons.iter().collect();
+    order.sort_by_key(|b| (b.y, b.x));
+
+    let button = match &config.focus_default {
+        Some(FocusTarget::None) => None,
+        Some(FocusTarget::First) => order.into_iter().find(sensitive),
+        Some(FocusTarget::Index(index)) => order.get(index - 1).copied().filter(sensitive),
+        Some(FocusTarget::Label(label)) => config
+            .button_config
+            .buttons
+            .iter()
+            .position(|b| &b.label == label)
+            .map(|index| &buttons[index])
+            .filter(sensitive),
+        None => config
+            .button_config
+            .buttons
+            .iter()
+            .position(|b| b.default && b.enabled)
+            .map(|index| &buttons[index])
+            .or_else(|| buttons.iter().find(sensitive)),
+    };
+
+    button.map(|b| b.button.clone())
+}
+
+/// Whether a mouse event happened over one of the buttons, disabled ones included
+fn on_button(menu: &Menu, window: &ApplicationWindow, e: &EventButton) -> bool {
+    let Some(gdk_window) = window.window() else {
+        return false;
+    };
+
+    let (_, origin_x, origin_y) = gdk_window.origin();
+
+    let (root_x, root_y) = e.root();
+    let (x, y) = (root_x as i32 - origin_x, root_y as i32 - origin_y);
+
+    menu.buttons.iter().any(|b| {
+        let Some((left, top)) = b.button.translate_coordinates(window, 0, 0) else {
+            return false;
+        };
+
+        let allocation = b.button.allocation();
+        (left..left + allocation.width()).contains(&x)
+            && (top..top + allocation.height()).contains(&y)
+    })
+}
+
+/// How long hovering a button does not take the focus after scrolling
+const SCROLL_FOCUS_GRACE: Duration = Duration::from_millis(500);
+
+fn app_main(config: &Arc<AppConfig>, app: &Application) {
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title("wleave")
+        .build();
+    window.style_context().add_class("menu");
+
+    if config.window_opacity < 1.0 {
+        window.set_opacity(config.window_opacity);
+    }
+
+    match config.protocol {
+        Protocol::LayerShell => {
+            window.init_layer_shell();
+            window.set_layer(gtk_layer_shell::Layer::Overlay);
+            window.set_namespace("wleave");
+            window.set_exclusive_zone(config.exclusive_zone);
+
+            // Exclusive keyboard focus would keep the menu focused when clicking
+            // beside a compact surface, so it could never lose the focus
+            window.set_keyboard_mode(if config.close_on_lost_focus {
+                gtk_layer_shell::KeyboardMode::OnDemand
+            } else {
+                gtk_layer_shell::KeyboardMode::Exclusive
+            });
+
+            for edge in &config.anchor {
+                window.set_anchor(layer_shell_edge(*edge), true);
+            }
+
+            let display = window.display();
+            let monitor = config
+                .output
+                .as_deref()
+                .and_then(|output| find_output(&display, output))
+                .map(|(_, monitor)| monitor);
+
+            // The other outputs can only be told apart from the one showing the menu
+            // when it is chosen here instead of by the compositor
+            let monitor = match monitor {
+                None if config.all_outputs => {
+                    display.primary_monitor().or_else(|| display.monitor(0))
+                }
+                monitor => monitor,
+            };
+
+            if let Some(monitor) = &monitor {
+                window.set_monitor(monitor);
+            }
+
+            if let Some(Geometry { width, height }) = config.geometry {
+                window.set_default_size(width, height);
+            }
+
+            if config.all_outputs {
+                if let Some(monitor) = monitor {
+                    add_backdrops(config, &window, monitor);
+                }
+            }
+        }
+        Protocol::Auto => unreachable!("The protocol is resolved before building the menu"),
+        Protocol::Xdg => match config.geometry {
+            Some(Geometry { width, height }) => {
+                window.set_default_size(width, height);
+                window.set_resizable(false);
+                window.set_position(gtk::WindowPosition::Center);
+            }
+            None => match config
+                .output
+                .as_deref()
+                .and_then(|output| find_output(&window.display(), output))
+            {
+                Some((number, _)) => {
+                    window.fullscreen_on_monitor(&window.display().default_screen(), number)
+                }
+                None => window.fullscreen(),
+            },
+        },
+    }
+
+    if config.close_on_lost_focus {
+        window.connect_focus_out_event(|window, _| {
+            if window.is_visible() {
+                window.close();
+            }
+
+            Propagation::Proceed
+        });
+    }
+
+    if config.inhibit_idle {
+        inhibit_idle(app, &window);
+    }
+
+    if let Some(cursor) = config.cursor.clone() {
+        window.connect_realize(move |window| apply_cursor(window, &cursor));
+    }
+
+    let grid = gtk::Grid::new();
+    let help = build_help(config);
+    let search_label = Label::builder()
+        .halign(gtk::Align::Center)
+        .valign(gtk::Align::Start)
+        .no_show_all(true)
+        .build();
+    search_label.style_context().add_class("search");
+
+    let overlay = gtk::Overlay::new();
+    overlay.add(&grid);
+    overlay.add_overlay(&search_label);
+    overlay.add_overlay(&help);
+    window.add(&overlay);
+
+    // Only the contents fade, input is handled right away all the same
+    if let Some(duration) = animation_duration(config) {
+        overlay.set_opacity(0.0);
+        let fade_overlay = overlay.clone();
+        window.connect_map(move |_| fade(&fade_overlay, 0.0, 1.0, duration, || {}));
+    }
+
+    grid.set_column_spacing(config.column_spacing);
+    grid.set_row_spacing(config.row_spacing);
+    apply_margins(config, &window, &grid);
+
+    // The monitor is only known for sure once the window is mapped
+    let cfg = config.clone();
+    let margin_grid = grid.clone();
+    window.connect_map(move |window| apply_margins(&cfg, window, &margin_grid));
+
+    let mut buttons = Vec::with_capacity(config.button_config.buttons.len());
+    let mut occupied = HashSet::new();
+    let mut next_cell = 0;
+
+    let per_row = columns(config, &window);
+    // Image icons are rendered for the scale of the output the menu opens on
+    let scale = window.scale_factor();
+    let mut separators = config.button_config.separators.iter().peekable();
+
+    let mut group = None;
+
+    for (index, bttn) in config.button_config.buttons.iter().enumerate() {
+        while separators.next_if(|&&before| before == index).is_some() {
+            next_cell = attach_separator(&grid, &occupied, next_cell, per_row);
+        }
+
+        // Each group starts on a new row, below its heading
+        if bttn.group.as_deref() != group {
+            group = bttn.group.as_deref();
+            let row = free_row(&occupied, next_cell, per_row);
+
+            next_cell = match group {
+                Some(name) => {
+                    let heading = Label::builder().label(name).xalign(0.0).build();
+                    heading.style_context().add_class("group-heading");
+                    grid.attach(&heading, 0, row as i32, per_row as i32, 1);
+                    (row + 1) * per_row
+                }
+                None => row * per_row,
+            };
+        }
+
+        let keybind = shown_keybind(config, index);
+        let label = match (config.show_keybinds, config.keybind_style) {
+            (false, _) => bttn.text.to_owned(),
+            (true, KeybindStyle::Mnemonic) => underline_keybind(&bttn.text, &keybind)
+                .unwrap_or_else(|| format!("{} {}", bttn.text, keybind_hint(config, &keybind))),
+            (true, KeybindStyle::Brackets) => {
+                format!("{} {}", bttn.text, keybind_hint(config, &keybind))
+            }
+        };
+
+        let button = gtk::Button::builder()
+            .label(&label)
+            .name(&bttn.label)
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+
+        // Fixed-size buttons are centered in their cell instead of filling it
+        if bttn.width_px.is_some() || bttn.height_px.is_some() {
+            button.set_size_request(
+                bttn.width_px.map_or(-1, |w| w as i32),
+                bttn.height_px.map_or(-1, |h| h as i32),
+            );
+        }
+
+        if bttn.width_px.is_some() {
+            button.set_halign(gtk::Align::Center);
+        }
+
+        if bttn.height_px.is_some() {
+            button.set_valign(gtk::Align::Center);
+        }
+
+        if let Some(label) = button.child() {
+            if let Some(label) = label.downcast_ref::<Label>() {
+                label.set_xalign(bttn.width);
+                label.set_yalign(bttn.height);
+                label.set_use_markup(true);
+                label.set_justify(bttn.justify.into());
+                shorten_label(
+                    label,
+                    bttn.wrap.unwrap_or(config.wrap),
+                    bttn.ellipsize.unwrap_or(config.ellipsize),
+                );
+            }
+        }
+
+        let image = bttn
+            .icon
+            .as_deref()
+            .filter(|_| !config.no_icons)
+            .and_then(|icon| load_icon(icon, config.icon_size, scale, bttn.icon_color.as_deref()));
+
+        let symbolic = image
+            .as_ref()
+            .and(bttn.icon_symbolic.as_deref())
+            .and_then(|icon| load_icon(icon, config.icon_size, scale, bttn.icon_color.as_deref()));
+
+        if let Some(color) = &bttn.icon_color {
+            for image in image.iter().chain(&symbolic) {
+                set_icon_color(image, color);
+            }
+        }
+
+        let image: Option<gtk::Widget> = match (image, symbolic) {
+            (Some(image), Some(symbolic)) => {
+                Some(symbolic_icon_stack(&button, image, symbolic).upcast())
+            }
+            (image, _) => image.map(|image| image.upcast()),
+        };
+
+        // Only hide the text when there is an icon to show instead
+        let hide_text = image.is_some() && bttn.hide_text.unwrap_or(config.icon_only);
+        if hide_text {
+            if let Some(accessible) = button.accessible() {
+                accessible.set_name(&plain_text(&bttn.text));
+            }
+        }
+
+        if let (Some(image), Some(text)) = (image, button.child()) {
+            button.remove(&text);
+
+            if hide_text && !config.show_keybinds {
+                button.add(&image);
+            } else {
+                if hide_text {
+                    if let Some(text) = text.downcast_ref::<Label>() {
+                        text.set_label(&keybind_hint(config, &keybind));
+                    }
+                }
+
+                add_icon_content(
+                    &button,
+                    image,
+                    text,
+                    bttn.icon_position.unwrap_or(config.icon_position),
+                );
+            }
+        }
+
+        let mut tooltip = keybind_tooltip(bttn.tooltip.as_deref().unwrap_or(&bttn.text), &keybind);
+
+        // Secondary actions cannot be reached with keys, so at least show what they do
+        let secondary = [
+            ("Right click", Click::Secondary),
+            ("Middle click", Click::Middle),
+            ("Long press", Click::LongPress),
+        ];
+        for (name, click) in secondary {
+            let steps = bttn.steps(click);
+            if !steps.is_empty() {
+                let commands: Vec<_> = steps.iter().map(Action::display).collect();
+                tooltip.push_str(&format!(
+                    "\n{name}: {}",
+                    markup_escape_text(&commands.join("; "))
+                ));
+            }
+        }
+
+        button.set_tooltip_markup(Some(&tooltip));
+
+        if bttn.circular {
+            button.style_context().add_class("circular");
+        }
+
+        if bttn.default {
+            button.style_context().add_class("default");
+        }
+
+        if !bttn.enabled {
+            button.set_sensitive(false);
+            button.style_context().add_class("disabled");
+        }
+
+        let hold = match bttn.hold_to_activate_ms {
+            Some(ms) if ms > 0 => {
+                let progress = gtk::ProgressBar::builder()
+                    .valign(gtk::Align::End)
+                    .no_show_all(true)
+                    .build();
+                progress.style_context().add_class("hold-progress");
+
+                Some(Rc::new(HoldActivation {
+                    duration: Duration::from_millis(ms.into()),
+                    progress,
+                    timer: RefCell::new(None),
+                }))
+            }
+            _ => None,
+        };
+
+        let countdown_label = match bttn.countdown_s {
+            Some(seconds) if seconds > 0 => {
+                let label = Label::builder().no_show_all(true).build();
+                label.style_context().add_class("countdown");
+                Some(label)
+            }
+            _ => None,
+        };
+
+        // Progress indicators are drawn over the button contents
+        if hold.is_some() || countdown_label.is_some() {
+            if let Some(child) = button.child() {
+                button.remove(&child);
+
+                let overlay = gtk::Overlay::new();
+                overlay.add(&child);
+                if let Some(hold) = &hold {
+                    overlay.add_overlay(&hold.progress);
+                }
+                if let Some(label) = &countdown_label {
+                    overlay.add_overlay(label);
+                }
+                button.add(&overlay);
+            }
+        }
+
+        let width = if bttn.col_span > per_row {
+            log!(
+                "Button {} spans {} columns, clamping to {per_row}",
+                bttn.label,
+                bttn.col_span
+            );
+            per_row
+        } else {
+            bttn.col_span.max(1)
+        };
+        let height = bttn.row_span.max(1);
+
+        // Flow the button into the first free spot that fits its span
+        let (x, y) = loop {
+            let (x, y) = (next_cell % per_row, next_cell / per_row);
+            let fits = x + width <= per_row
+                && (y..y + height).all(|cy| (x..x + width).all(|cx| !occupied.contains(&(cx, cy))));
+
+            if fits {
+                break (x, y);
+            }
+
+            next_cell += 1;
+        };
+
+        for cy in y..y + height {
+            for cx in x..x + width {
+                occupied.insert((cx, cy));
+            }
+        }
+
+        grid.attach(&button, x as i32, y as i32, width as i32, height as i32);
+        // Holding the button already has a meaning with hold-to-activate
+        if bttn.action_long.is_some() && hold.is_some() {
+            log!(
+                "Warning: button {} has hold-to-activate-ms, ignoring its action-long",
+                bttn.label
+            );
+        }
+
+        buttons.push(GridButton {
+            button,
+            hold,
+            countdown_label,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    for _ in separators {
+        next_cell = attach_separator(&grid, &occupied, next_cell, per_row);
+    }
+
+    let menu = Rc::new(Menu {
+        buttons,
+        armed: RefCell::new(None),
+        countdown: RefCell::new(None),
+        help,
+        filter: RefCell::new(String::new()),
+        search_label,
+        pending_g: Cell::new(false),
+        held_keys: RefCell::new(HashSet::new()),
+        activated: Cell::new(false),
+        swipe: config
+            .swipe_close_distance
+            .map(|distance| swipe_to_close(config, &window, &grid, distance)),
+    });
+
+    let initial_focus = initial_focus(config, &menu.buttons);
+
+    // The pointer may already rest on a button when the menu appears, only
+    // follow it once it has moved so that the initial focus is kept
+    let pointer_moved = Rc::new(Cell::new(false));
+    // Scrolling takes precedence over the hovered button for a moment, so
+    // that a slightly moved pointer does not take the focus back right away
+    let scrolled_at = Rc::new(Cell::new(None::<Instant>));
+    if config.focus_follows_hover {
+        let moved = pointer_moved.clone();
+        window.add_events(EventMask::POINTER_MOTION_MASK);
+        window.connect_motion_notify_event(move |_, _| {
+            moved.set(true);
+            Propagation::Proceed
+        });
+    }
+
+    for (index, b) in menu.buttons.iter().enumerate() {
+        if config.focus_follows_hover {
+            let moved = pointer_moved.clone();
+            let scrolled_at = scrolled_at.clone();
+            b.button.connect_enter_notify_event(move |button, _| {
+                let scrolling = scrolled_at
+                    .get()
+                    .is_some_and(|at| at.elapsed() < SCROLL_FOCUS_GRACE);
+
+                if moved.get() && !scrolling && button.is_sensitive() && !button.is_focus() {
+                    button.grab_focus();
+                }
+                Propagation::Proceed
+            });
+        }
+
+        b.button.connect_focus_in_event(|button, _| {
+            button.style_context().add_class("focused");
+            Propagation::Proceed
+        });
+        b.button.connect_focus_out_event(|button, _| {
+            button.style_context().remove_class("focused");
+            Propagation::Proceed
+        });
+
+        let bttn = &config.button_config.buttons[index];
+        if bttn.action_right.is_some() || bttn.action_middle.is_some() {
+            let cfg = config.clone();
+            let menu_handle = Rc::downgrade(&menu);
+            let window_handle = window.clone();
+            b.button.connect_button_press_event(move |_, e| {
+                let click = Click::from_mouse_button(e.button())
+                    .filter(|&click| click != Click::Primary)
+                    .filter(|&click| cfg.button_config.buttons[index].action(click).is_some());
+
+                // Stopping the press keeps it from reaching the window
+                match (click, menu_handle.upgrade()) {
+                    (Some(click), Some(menu)) => {
+                        activate_button(&cfg, &menu, index, click, &window_handle);
+                        Propagation::Stop
+                    }
+                    _ => Propagation::Proceed,
+                }
+            });
+        }
+
+        let Some(hold) = &b.hold else {
+            // The release ending a long press still clicks the button
+            let long_pressed = Rc::new(Cell::new(false));
+
+            if bttn.action_long.is_some() {
+                // Releasing outside of the button does not click it, so start over on every press
+                let pressed = long_pressed.clone();
+                b.button.connect_button_press_event(move |_, _| {
+                    pressed.set(false);
+                    Propagation::Proceed
+                });
+
+                let cfg = config.clone();
+                let menu_handle = Rc::downgrade(&menu);
+                let window_handle = window.clone();
+                let pressed = long_pressed.clone();
+                connect_long_press(&b.button, bttn.long_press_ms, move || {
+                    if let Some(menu) = menu_handle.upgrade() {
+                        pressed.set(true);
+                        activate_button(&cfg, &menu, index, Click::LongPress, &window_handle);
+                    }
+                });
+            }
+
+            let cfg = config.clone();
+            let menu_handle = Rc::downgrade(&menu);
+            let window_handle = window.clone();
+            b.button.connect_clicked(move |_| {
+                if long_pressed.replace(false) {
+                    return;
+                }
+
+                if let Some(menu) = menu_handle.upgrade() {
+                    activate_button(&cfg, &menu, index, Click::Primary, &window_handle);
+                }
+            });
+            continue;
+        };
+
+        let hold_press = hold.clone();
+        let cfg = config.clone();
+        let menu_handle = Rc::downgrade(&menu);
+        let window_handle = window.clone();
+        b.button.connect_button_press_event(move |button, e| {
+            match menu_handle.upgrade() {
+                Some(menu)
+                    if Click::from_mouse_button(e.button()) == Some(Click::Primary)
+                        && !menu.is_counting_down() =>
+                {
+                    let cfg = cfg.clone();
+                    let window_handle = window_handle.clone();
+                    hold_press.start(button, move || {
+                        run_button(&cfg, &menu, index, Click::Primary, &window_handle)
+                    });
+                }
+                _ => {}
+            }
+
+            Propagation::Proceed
+        });
+
+        let hold_release = hold.clone();
+        b.button.connect_button_release_event(move |button, _| {
+            hold_release.cancel(button);
+            Propagation::Proceed
+        });
+
+        let hold_leave = hold.clone();
+        b.button.connect_leave_notify_event(move |button, _| {
+            hold_leave.cancel(button);
+            Propagation::Proceed
+        });
+    }
+
+    // Countdowns pause while the window is unfocused
+    let focus_menu = menu.clone();
+    window.connect_focus_out_event(move |_, _| {
+        focus_menu.pause_countdown();
+        // Keys released elsewhere never report it here
+        focus_menu.held_keys.borrow_mut().clear();
+        Propagation::Proceed
+    });
+
+    let focus_menu = menu.clone();
+    window.connect_focus_in_event(move |_, _| {
+        focus_menu.resume_countdown();
+        Propagation::Proceed
+    });
+
+    let idle = config
+        .timeout_s
+        .filter(|&seconds| seconds > 0)
+        .map(|seconds| close_when_idle(config, &menu, &window, &overlay, seconds));
+
+    let cfg = config.clone();
+    let key_menu = menu.clone();
+    window.connect_key_press_event(move |window, e| {
+        reset_then_handle(idle.as_deref(), || handle_key(&cfg, window, &key_menu, e))
+    });
+
+    if let Some(ms) = config.timeout_ms.filter(|&ms| ms > 0) {
+        let menu = Rc::downgrade(&menu);
+        let window = window.clone();
+        timeout_add_local_once(Duration::from_millis(ms.into()), move || {
+            // A selected button closes the menu on its own
+            let selected = menu
+                .upgrade()
+                .is_none_or(|menu| menu.activated.get() || menu.is_counting_down());
+
+            if !selected && window.is_visible() {
+                window.close();
+            }
+        });
+    }
+
+    #[cfg(feature = "gamepad")]
+    {
+        let cfg = config.clone();
+        let menu_handle = Rc::downgrade(&menu);
+        let window_handle = window.clone();
+        gamepad::watch(move |input| {
+            // The menu goes away along with the window
+            let Some(menu) = menu_handle.upgrade() else {
+                return ControlFlow::Break;
+            };
+
+            match input {
+                gamepad::Input::Move(direction) => {
+                    move_focus(&menu.buttons, &window_handle, direction);
+                }
+                gamepad::Input::Activate => {
+                    let focused = menu.buttons.iter().position(|b| b.button.is_focus());
+                    if let Some(index) = focused {
+                        activate_button(&cfg, &menu, index, Click::Primary, &window_handle);
+                    }
+                }
+                gamepad::Input::Cancel => {
+                    if dismiss(&menu, &window_handle) {
+                        return ControlFlow::Break;
+                    }
+                }
+            }
+
+            ControlFlow::Continue
+        });
+    }
+
+    // Letting go of a key cancels any press-and-hold started with it
+    let key_menu = menu.clone();
+    window.connect_key_release_event(move |_, e| {
+        key_menu
+            .held_keys
+            .borrow_mut()
+            .remove(&e.hardware_keycode());
+
+        for b in &key_menu.buttons {
+            if let Some(hold) = &b.hold {
+                hold.cancel(&b.button);
+            }
+        }
+
+        Propagation::Proceed
+    });
+
+    // The mouse buttons closing the menu when clicked outside of the buttons
+    let click_away: Vec<_> = [
+        (config.close_on_click_away, Click::Primary),
+        (config.close_on_right_click, Click::Secondary),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, click)| enabled.then_some(click))
+    .collect();
+
+    if !click_away.is_empty() {
+        // A click that starts or ends on a button does not close the menu
+        let pressed_on_button = Rc::new(Cell::new(false));
+        let pressed = pressed_on_button.clone();
+        let press_menu = menu.clone();
+        let press_clicks = click_away.clone();
+        window.add_events(EventMask::BUTTON_PRESS_MASK | EventMask::BUTTON_RELEASE_MASK);
+        window.connect_button_press_event(move |window, e| {
+            let click = Click::from_mouse_button(e.button());
+            if click.is_some_and(|click| press_clicks.contains(&click)) {
+                pressed.set(on_button(&press_menu, window, e));
+            }
+            Propagation::Proceed
+        });
+
+        let release_menu = menu.clone();
+        window.connect_button_release_event(move |window, e| {
+            let click = Click::from_mouse_button(e.button());
+            if !click.is_some_and(|click| click_away.contains(&click)) {
+                return Propagation::Proceed;
+            }
+
+            if !pressed_on_button.get() && !on_button(&release_menu, window, e) {
+                dismiss(&release_menu, window);
+            }
+            Propagation::Proceed
+        });
+    }
+
+    if config.scroll_nav {
+        let scroll_menu = menu.clone();
+        window.add_events(EventMask::SCROLL_MASK);
+        window.connect_scroll_event(move |window, e| {
+            let step = match e.direction() {
+                ScrollDirection::Up | ScrollDirection::Left => -1,
+                ScrollDirection::Down | ScrollDirection::Right => 1,
+                _ => return Propagation::Proceed,
+            };
+
+            scrolled_at.set(Some(Instant::now()));
+            window.set_focus_visible(true);
+            cycle_focus(&scroll_menu.buttons, step);
+            Propagation::Stop
+        });
+
+        // Buttons only react to the primary button, so a middle click
+        // anywhere reaches the window and activates the focused button
+        let cfg = config.clone();
+        window.add_events(EventMask::BUTTON_PRESS_MASK);
+        window.connect_button_press_event(move |window, e| {
+            if Click::from_mouse_button(e.button()) != Some(Click::Middle) {
+                return Propagation::Proceed;
+            }
+
+            if let Some(index) = menu.buttons.iter().position(|b| b.button.is_focus()) {
+                activate_button(&cfg, &menu, index, Click::Primary, window);
+            }
+            Propagation::Stop
+        });
+    }
+
+    window.show_all();
+
+    if let Some(button) = initial_focus {
+        button.grab_focus();
+    }
+}
+
+/// Brings the menu to the front, only building a new window when none is shown,
+/// so that invoking wleave again re-presents the running instance
+fn present_menu(config: &Arc<AppConfig>, app: &Application) {
+    let menu = app
+        .windows()
+        .into_iter()
+        .find(|w| w.is_visible() && !w.style_context().has_class("backdrop"));
+
+    match menu {
+        Some(window) => window.present(),
+        None => app_main(config, app),
+    }
+}
+
+fn apply_color_scheme(color_scheme: ColorScheme) {
+    let prefer_dark = match color_scheme {
+        ColorScheme::Default => return,
+        ColorScheme::Light => false,
+        ColorScheme::Dark => true,
+    };
+
+    match gtk::Settings::default() {
+        Some(settings) => settings.set_gtk_application_prefer_dark_theme(prefer_dark),
+        None => log!("Failed to apply the color scheme: no GTK settings available"),
+    }
+}
+
+/// Checks that the compositor supports layer-shell when it is requested, since
+/// the window would otherwise stay blank or not show up at all, and resolves
+/// `Protocol::Auto` to one of the others
+fn select_protocol(protocol: Protocol, fallback: bool) -> Result<Protocol, String> {
+    if let Protocol::Xdg = protocol {
+        return Ok(protocol);
+    }
+
+    gtk::init().map_err(|e| format!("Failed to initialize GTK: {e}"))?;
+
+    // Also false for X11 displays, such as with GDK_BACKEND=x11
+    if gtk_layer_shell::is_supported() {
+        Ok(Protocol::LayerShell)
+    } else if let Protocol::Auto = protocol {
+        log!("Warning: the display does not support the layer-shell protocol, using xdg");
+        Ok(Protocol::Xdg)
+    } else if fallback {
+        log!("The compositor does not support the layer-shell protocol, falling back to xdg");
+        Ok(Protocol::Xdg)
+    } else {
+        Err("The compositor does not support the layer-shell protocol".to_owned())
+    }
+}
+
+/// Combines the options with the layout, choosing the margins for the protocol
+fn app_config(args: &Args, protocol: Protocol, button_config: WButtonConfig) -> AppConfig {
+    // Margins for the active protocol replace the ones for all sides
+    let (margin, margin_percent) = match protocol {
+        Protocol::Xdg => (args.xdg_margin, args.xdg_margin_percent),
+        Protocol::LayerShell => (args.layer_shell_margin, args.layer_shell_margin_percent),
+        Protocol::Auto => (None, None),
+    };
+    let (margin, margin_percent) = if margin.is_some() || margin_percent.is_some() {
+        (margin, margin_percent)
+    } else {
+        (args.margin, args.margin_percent)
+    };
+
+    // The default margin is meant for a fullscreen menu and leaves no room in a sized one
+    let margin = match (margin, margin_percent, args.geometry) {
+        (None, None, Some(_)) => Some(0),
+        _ => margin,
+    };
+
+    AppConfig {
+        margin_top: Margin::select(
+            args.margin_top,
+            args.margin_top_percent,
+            margin,
+            margin_percent,
+        ),
+        margin_bottom: Margin::select(
+            args.margin_bottom,
+            args.margin_bottom_percent,
+            margin,
+            margin_percent,
+        ),
+        margin_left: Margin::select(
+            args.margin_left,
+            args.margin_left_percent,
+            margin,
+            margin_percent,
+        ),
+        margin_right: Margin::select(
+            args.margin_right,
+            args.margin_right_percent,
+            margin,
+            margin_percent,
+        ),
+        row_spacing: args.row_spacing,
+        column_spacing: args.column_spacing,
+        protocol,
+        geometry: args.geometry,
+        window_opacity: args.window_opacity,
+        timeout_s: args.timeout_s,
+        timeout_action: args.timeout_action.clone(),
+        timeout_ms: args.timeout_ms,
+        inhibit_idle: args.inhibit_idle,
+        animation_ms: args.animation_ms,
+        reduced_motion: args.reduced_motion,
+        output: args.output.clone(),
+        all_outputs: args.all_outputs,
+        anchor: args
+            .position
+            .map_or_else(|| args.anchor.clone(), |p| p.anchor()),
+        exclusive_zone: args.exclusive_zone,
+        buttons_per_row: args.buttons_per_row,
+        min_button_width: args.min_button_width,
+        close_on_lost_focus: args.close_on_lost_focus,
+        close_on_right_click: args.close_on_right_click,
+        close_on_click_away: args.close_on_click_away,
+        show_keybinds: args.show_keybinds,
+        keybind_style: args.keybind_style,
+        keybind_format: args.keybind_format.clone(),
+        wrap: args.wrap,
+        ellipsize: args.ellipsize,
+        keybind_confirm: args.keybind_confirm,
+        keybind_confirm_timeout: Duration::from_millis(args.keybind_confirm_timeout_ms.into()),
+        scroll_nav: args.scroll_nav,
+        swipe_close_distance: args.swipe_close_distance,
+        ordinal_keybinds: args.ordinal_keybinds,
+        focus_follows_hover: args.focus_follows_hover,
+        vim_keys: args.vim_keys,
+        search: args.search,
+        keybinds_by_position: args.keybinds_by_position,
+        keybinds_ignore_case: args.keybinds_ignore_case,
+        cursor: args.cursor.clone(),
+        help_key: args.help_key.clone(),
+        focus_default: args.focus_default.clone(),
+        cancel_keys: args.cancel_keys.clone(),
+        icon_size: args.icon_size,
+        icon_position: args.icon_position,
+        icon_only: args.icon_only,
+        no_icons: args.no_icons,
+        pre_action: args.pre_action.clone().map(Action::Shell),
+        wait_pre_action: args.wait_pre_action,
+        post_action: args.post_action.clone().map(Action::Shell),
+        notify_command: args.notify_command.clone().map(Action::Shell),
+        button_config,
+        delay_ms: args.delay_command_ms,
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    log::set_format(args.log_format);
+
+    if let Some(log_file) = &args.log_file {
+        if let Err(e) = log::init(log_file) {
+            log!("{e}");
+        }
+    }
+
+    let button_config = match &args.layout_command {
+        Some(command) => load_config_from_command(command, args.strict),
+        None => load_config(args.layout.as_ref(), args.strict),
+    };
+
+    let mut button_config = match button_config {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            log!("Failed to load config: {e}");
+            return ExitCode::from(EXIT_CONFIG_ERROR);
+        }
+    };
+
+    run_startup_commands(
+        &mut button_config.buttons,
+        Duration::from_millis(args.startup_command_timeout_ms.into()),
+    );
+
+    if args.vim_keys {
+        warn_vim_key_conflicts(&button_config.buttons, args.keybinds_ignore_case);
+    }
+
+    if args.keybinds_ignore_case {
+        warn_case_conflicts(&button_config.buttons);
+    }
+
+    warn_help_key_conflicts(
+        &button_config.buttons,
+        &args.help_key,
+        args.keybinds_ignore_case,
+    );
+
+    if let Err(e) = check_cancel_keys(
+        &args.cancel_keys,
+        &button_config.buttons,
+        args.keybinds_ignore_case,
+    ) {
+        log!("Failed to load config: {e}");
+        return ExitCode::from(EXIT_CONFIG_ERROR);
+    }
+
+    if let Some(label) = &args.timeout_action {
+        if !button_config.buttons.iter().any(|b| &b.label == label) {
+            log!("Failed to load config: The timeout action {label} matches no button label");
+            return ExitCode::from(EXIT_CONFIG_ERROR);
+        }
+    }
+
+    if let Some(target) = &args.focus_default {
+        if let Err(e) = check_focus_target(target, &button_config.buttons) {
+            log!("Failed to load config: {e}");
+            return ExitCode::from(EXIT_CONFIG_ERROR);
+        }
+    }
+
+    let background = match background_css(args.background_color.as_deref(), args.background_opacity)
+    {
+        Ok(background) => background,
+        Err(e) => {
+            log!("Failed to load config: {e}");
+            return ExitCode::from(EXIT_CONFIG_ERROR);
+        }
+    };
+
+    if !(0.0..=1.0).contains(&args.window_opacity) {
+        log!(
+            "Failed to load config: The window opacity must be between 0.0 and 1.0, got {}",
+            args.window_opacity
+        );
+        return ExitCode::from(EXIT_CONFIG_ERROR);
+    }
+
+    // Detecting the protocol needs a display, while printing the config must
+    // work without one, so it shows the protocol as given
+    let protocol = if args.print_config {
+        args.protocol
+    } else {
+        match select_protocol(args.protocol, args.layer_shell_fallback) {
+            Ok(protocol) => protocol,
+            Err(e) => {
+                log!("{e}");
+                return ExitCode::from(EXIT_CONFIG_ERROR);
+            }
+        }
+    };
+
+    let config = Arc::new(app_config(&args, protocol, button_config));
+
+    if args.print_config {
+        return match serde_json::to_string_pretty(&*config) {
+            Ok(json) => {
+                println!("{json}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                log!("Failed to print the config: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let app = Application::builder()
+        .application_id("sh.natty.Wleave")
+        .build();
+
+    let color_scheme = args.color_scheme;
+    app.connect_startup(move |_| {
+        apply_color_scheme(color_scheme);
+
+        let screen = Screen::default().expect("Could not connect to a display.");
+
+        // Without a stylesheet of the user's, only the GTK theme styles the menu
+        match load_css(args.css.as_ref(), args.no_default_css) {
+            Ok(css) => StyleContext::add_provider_for_screen(
+                &screen,
+                &css,
+                gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+            ),
+            Err(e) => log!("Failed to load CSS: {e}"),
+        }
+
+        // Above the stylesheet, which sets a background of its own,
+        // but below the CSS of the user's GTK settings
+        if let Some(background) = &background {
+            let provider = CssProvider::new();
+            match provider.load_from_data(background.as_bytes()) {
+                Ok(_) => StyleContext::add_provider_for_screen(
+                    &screen,
+                    &provider,
+                    gtk::STYLE_PROVIDER_PRIORITY_APPLICATION + 1,
+                ),
+                Err(e) => log!("Failed to apply the background: {e}"),
+            }
+        }
+    });
+
+    if args.close_on_session_lock {
+        app.connect_startup(|app| {
+            let app = app.downgrade();
+            logind::watch_lock(move || {
+                let Some(app) = app.upgrade() else {
+                    return;
+                };
+
+                for window in app.windows() {
+                    window.close();
+                }
+            });
+        });
+    }
+
+    #[cfg(feature = "dbus")]
+    {
+        let cfg = config.clone();
+        app.connect_startup(move |app| {
+            let cfg = cfg.clone();
+            dbus::register_actions(app, move |app| present_menu(&cfg, app));
+        });
+    }
+
+    let _hold = args.daemon.then(|| app.hold());
+
+    // A daemon starts hidden until it is invoked again
+    let start_hidden = std::cell::Cell::new(args.daemon);
+
+    app.connect_activate(move |app| {
+        if !start_hidden.replace(false) {
+            present_menu(&config, app);
+        }
+    });
+
+    let status = i32::from(app.run_with_args(&[] as &[&str]));
+
+    // Another instance showing its menu is as good as showing one here
+    if status != 0 || app.is_remote() || SELECTED.load(Ordering::Relaxed) {
+        ExitCode::from(status as u8)
+    } else {
+        ExitCode::from(EXIT_CANCELLED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{mpsc, Mutex, OnceLock};
+
+    type GtkJob = Box<dyn FnOnce() + Send>;
+    type GtkJobs = Mutex<mpsc::Sender<(GtkJob, mpsc::Sender<std::thread::Result<()>>)>>;
+
+    /// Runs `test` on the only thread GTK may be used from, skipping it when
+    /// there is no display to test with
+    fn with_gtk(test: impl FnOnce() + Send + 'static) {
+        static GTK: OnceLock<Option<GtkJobs>> = OnceLock::new();
+
+        let gtk = GTK.get_or_init(|| {
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let (jobs_tx, jobs_rx) = mpsc::channel::<(GtkJob, mpsc::Sender<_>)>();
+
+            std::thread::spawn(move || {
+                let _ = ready_tx.send(gtk::init().is_ok());
+                for (job, done) in jobs_rx {
+                    let _ = done.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)));
+                }
+            });
+
+            ready_rx.recv().unwrap().then(|| Mutex::new(jobs_tx))
+        });
+
+        let Some(jobs) = gtk else {
+            eprintln!("Skipping a test that needs a display");
+            return;
+        };
+
+        let (done_tx, done_rx) = mpsc::channel();
+        jobs.lock()
+            .unwrap()
+            .send((Box::new(test), done_tx))
+            .unwrap();
+        if let Err(panic) = done_rx.recv().unwrap() {
+            std::panic::resume_unwind(panic);
+        }
+    }
+
+    /// Runs the main loop until `done` holds, false when that took too long
+    fn run_until(done: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        let context = glib::MainContext::default();
+
+        while !done() {
+            if start.elapsed() > Duration::from_secs(5) {
+                return false;
+            }
+            context.iteration(false);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        true
+    }
+
+    /// A registered application, since windows can only be added after its startup
+    fn test_app() -> Application {
+        let app = Application::builder()
+            .application_id("sh.natty.WleaveTest")
+            .flags(gio::ApplicationFlags::NON_UNIQUE)
+            .build();
+        app.register(gio::Cancellable::NONE).unwrap();
+        app
+    }
+
+    fn test_config(layout: &str, args: &[&str]) -> Arc<AppConfig> {
+        let args = Args::parse_from(["wleave"].iter().chain(args));
+        Arc::new(app_config(&args, Protocol::Xdg, parse_layout(layout)))
+    }
+
+    #[test]
+    fn justify_ignores_case() {
+        let cases = [
+            ("center", Justify::Center),
+            ("Center", Justify::Center),
+            ("CENTER", Justify::Center),
+            ("fill", Justify::Fill),
+            ("Fill", Justify::Fill),
+            ("FILL", Justify::Fill),
+            ("left", Justify::Left),
+            ("Left", Justify::Left),
+            ("LEFT", Justify::Left),
+            ("right", Justify::Right),
+            ("Right", Justify::Right),
+            ("rIgHt", Justify::Right),
+        ];
+
+        for (json, justify) in cases {
+            let parsed: Justify = serde_json::from_value(serde_json::json!(json)).unwrap();
+            assert_eq!(parsed, justify, "{json}");
+        }
+    }
+
+    #[test]
+    fn justify_rejects_unknown_values() {
+        let error = serde_json::from_value::<Justify>(serde_json::json!("centre")).unwrap_err();
+        assert!(error.to_string().contains("centre"), "{error}");
+    }
+
+    #[test]
+    fn invalid_justify_names_the_entry() {
+        let layout = r#"
+            {"label": "lock", "action": "true", "text": "Lock", "keybind": "l"}
+            {"label": "logout", "action": "true", "text": "Logout", "keybind": "e", "justify": "centre"}
+        "#;
+
+        let error = parse_layout_entries(
+            layout.as_bytes(),
+            Path::new("layout"),
+            false,
+            &mut Vec::new(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+
+        assert!(error.contains("entry 2 of layout"), "{error}");
+    }
+
+    /// Parses a layout given as JSON text the way a layout file is loaded
+    fn parse_layout(layout: &str) -> WButtonConfig {
+        let mut entries = Vec::new();
+        parse_layout_entries(
+            layout.as_bytes(),
+            Path::new("layout"),
+            false,
+            &mut Vec::new(),
+            &mut entries,
+        )
+        .unwrap();
+
+        build_button_config(entries).unwrap()
+    }
+
+    /// A path in the temporary directory that no other test uses
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wleave-test-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn actions_see_button_env() {
+        let out = temp_path("env");
+        std::env::set_var("WLEAVE_TEST_DIR", "/tmp/wleave");
+
+        let layout = serde_json::json!({
+            "label": "lock",
+            "action": ["sh", "-c", "env > \"$OUT\""],
+            "text": "Lock",
+            "keybind": "l",
+            "env": {
+                "OUT": out,
+                "GREETING": "hello",
+                "LOCK_DIR": "${WLEAVE_TEST_DIR}/lock",
+                "LOCK_FILE": "$WLEAVE_TEST_DIR/lock.pid",
+                "UNSET": "${WLEAVE_TEST_UNSET}",
+            },
+        });
+        let config = parse_layout(&layout.to_string());
+        let button = &config.buttons[0];
+
+        let mut child = run_command(&button.steps(Click::Primary)[0], &button.env).unwrap();
+        assert!(child.wait().unwrap().success());
+
+        let env = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&out).unwrap();
+
+        let lines: Vec<_> = env.lines().collect();
+        assert!(lines.contains(&"GREETING=hello"), "{env}");
+        assert!(lines.contains(&"LOCK_DIR=/tmp/wleave/lock"), "{env}");
+        assert!(lines.contains(&"LOCK_FILE=/tmp/wleave/lock.pid"), "{env}");
+        assert!(lines.contains(&"UNSET="), "{env}");
+    }
+
+    #[test]
+    fn close_modes_order_action_and_window() {
+        with_gtk(|| {
+            // The delay is skipped without animations
+            if let Some(settings) = gtk::Settings::default() {
+                settings.set_gtk_enable_animations(true);
+            }
+
+            let marker = temp_path("close-mode");
+            let modes = [
+                (CloseMode::HideThenRun, "hide-then-run", "h"),
+                (CloseMode::RunThenClose, "run-then-close", "c"),
+                (CloseMode::RunAndStay, "run-and-stay", "s"),
+            ];
+            let layout: String = modes
+                .iter()
+                .map(|(_, mode, keybind)| {
+                    serde_json::json!({
+                        "label": mode,
+                        "action": ["sh", "-c", "echo \"$0\" > \"$1\"", mode, marker],
+                        "text": mode,
+                        "keybind": keybind,
+                        "close-mode": mode,
+                    })
+                    .to_string()
+                })
+                .collect();
+            let config = test_config(&layout, &["--delay-command-ms", "500"]);
+
+            for (index, (mode, name, _)) in modes.into_iter().enumerate() {
+                let _ = std::fs::remove_file(&marker);
+                let app = test_app();
+                let window = ApplicationWindow::new(&app);
+                window.show();
+
+                on_option(&config, index, Click::Primary, window.clone());
+                let ran = || marker.is_file();
+
+                match mode {
+                    CloseMode::HideThenRun => {
+                        // Hidden right away, the action only follows after the delay
+                        assert!(!window.is_visible(), "{name}");
+                        assert!(!ran(), "{name}");
+                        assert!(run_until(ran), "{name}");
+                    }
+                    CloseMode::RunThenClose => {
+                        assert!(run_until(ran), "{name}");
+                        assert!(window.is_visible(), "{name}");
+                        assert!(run_until(|| !window.is_visible()), "{name}");
+                    }
+                    CloseMode::RunAndStay => {
+                        assert!(run_until(ran), "{name}");
+                        let waited = Instant::now();
+                        run_until(|| waited.elapsed() > Duration::from_secs(1));
+                        assert!(window.is_visible(), "{name}");
+                        window.close();
+                    }
+                }
+
+                let written = std::fs::read_to_string(&marker).unwrap();
+                assert_eq!(written.trim(), name);
+            }
+
+            let _ = std::fs::remove_file(&marker);
+        });
+    }
+
+    #[test]
+    fn rapid_activations_spawn_once() {
+        with_gtk(|| {
+            let marker = temp_path("debounce");
+            let _ = std::fs::remove_file(&marker);
+            let layout = serde_json::json!({
+                "label": "lock",
+                "action": ["sh", "-c", "echo x >> \"$0\"", marker],
+                "text": "Lock",
+                "keybind": "l",
+            })
+            .to_string();
+            let config = test_config(&layout, &[]);
+
+            let app = test_app();
+            let window = ApplicationWindow::new(&app);
+            window.show();
+            let menu = Rc::new(Menu {
+                buttons: vec![GridButton {
+                    button: gtk::Button::new(),
+                    hold: None,
+                    countdown_label: None,
+                    x: 0,
+                    y: 0,
+                    width: 1,
+                    height: 1,
+                }],
+                armed: RefCell::new(None),
+                countdown: RefCell::new(None),
+                help: gtk::Grid::new(),
+                filter: RefCell::new(String::new()),
+                search_label: Label::new(None),
+                pending_g: Cell::new(false),
+                held_keys: RefCell::new(HashSet::new()),
+                activated: Cell::new(false),
+                swipe: None,
+            });
+
+            activate_button(&config, &menu, 0, Click::Primary, &window);
+            activate_button(&config, &menu, 0, Click::Primary, &window);
+
+            assert!(run_until(|| marker.is_file()));
+            // Leave a second spawn the time to show up
+            let waited = Instant::now();
+            run_until(|| waited.elapsed() > Duration::from_millis(500));
+
+            let written = std::fs::read_to_string(&marker).unwrap();
+            assert_eq!(written.lines().count(), 1);
+
+            let _ = std::fs::remove_file(&marker);
+        });
+    }
+
+    #[test]
+    fn exclusive_zone_keeps_margins() {
+        let layout = r#"{"label": "lock", "action": "true", "text": "Lock", "keybind": "l"}"#;
+        let config = test_config(
+            layout,
+            &[
+                "--exclusive-zone",
+                "0",
+                "--margin",
+                "40",
+                "--margin-top-percent",
+                "10",
+            ],
+        );
+
+        assert_eq!(config.exclusive_zone, 0);
+        assert!(matches!(config.margin_left, Margin::Pixels(40)));
+        assert!(matches!(config.margin_bottom, Margin::Pixels(40)));
+        // Percentages are taken of the output, not of the space left by panels
+        assert!(matches!(config.margin_top, Margin::Percent(p) if p == 10.0));
+        assert_eq!(config.margin_top.resolve(1080), 108);
+    }
+
+    #[test]
+    fn exclusive_zone_below_overlay_is_rejected() {
+        assert!(Args::try_parse_from(["wleave", "--exclusive-zone", "-1"]).is_ok());
+        assert!(Args::try_parse_from(["wleave", "--exclusive-zone", "-2"]).is_err());
+    }
+
+    #[test]
+    fn recolor_keeps_transparency() {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, true, 8, 3, 2).unwrap();
+        pixbuf.fill(0xff000000);
+        pixbuf.put_pixel(1, 1, 0x20, 0x40, 0x60, 0xff);
+
+        let recolored = recolor_pixbuf(&pixbuf, "rgba(0, 0, 255, 0.5)");
+        let pixels = recolored.read_pixel_bytes();
+        let rowstride = recolored.rowstride() as usize;
+
+        // Transparent pixels stay transparent, opaque ones take on the color
+        assert_eq!(&pixels[..4], &[0, 0, 255, 0]);
+        assert_eq!(&pixels[rowstride + 4..rowstride + 8], &[0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn cancel_keys_follow_keybind_case() {
+        let layout = r#"{"label": "quit", "action": "true", "text": "Quit", "keybind": "Q"}"#;
+        let buttons = parse_layout(layout).buttons;
+        let cancel_keys = ["q".to_string()];
+
+        assert!(check_cancel_keys(&cancel_keys, &buttons, true).is_err());
+        assert!(check_cancel_keys(&cancel_keys, &buttons, false).is_ok());
+    }
+
+    #[test]
+    fn tooltip_shows_keybind_when_set() {
+        assert_eq!(keybind_tooltip("Lock", "l"), "Lock (l)");
+        assert_eq!(keybind_tooltip("Lock", "<"), "Lock (&lt;)");
+        assert_eq!(keybind_tooltip("Lock", ""), "Lock");
+    }
+
+    #[test]
+    fn vim_key_conflicts_follow_keybind_case() {
+        let layout = r#"
+            {"label": "lock", "action": "true", "text": "Lock", "keybind": "J"}
+            {"label": "logout", "action": "true", "text": "Logout", "keybind": "e"}
+        "#;
+        let buttons = parse_layout(layout).buttons;
+        let labels = |ignore_case| {
+            vim_key_conflicts(&buttons, ignore_case)
+                .map(|b| b.label.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(labels(true), ["lock"]);
+        assert!(labels(false).is_empty());
+    }
+
+    #[test]
+    fn cancel_keys_ignore_case_like_keybinds() {
+        let layout = r#"{"label": "lock", "action": "true", "text": "Lock", "keybind": "l"}"#;
+
+        let config = test_config(layout, &["--cancel-keys", "q"]);
+        assert!(is_cancel_key(&config, "Q"));
+
+        let config = test_config(
+            layout,
+            &["--cancel-keys", "q", "--keybinds-ignore-case", "false"],
+        );
+        assert!(is_cancel_key(&config, "q"));
+        assert!(!is_cancel_key(&config, "Q"));
+    }
+
+    #[test]
+    fn idle_countdown_elapses() {
+        let idle = IdleCountdown::new(2, |_| {});
+
+        assert!(!idle.tick());
+        assert!(idle.tick());
+    }
+
+    #[test]
+    fn stopped_key_resets_idle_countdown() {
+        let shown = Rc::new(Cell::new(0));
+        let show = shown.clone();
+        let idle = IdleCountdown::new(5, move |left| show.set(left));
+
+        idle.tick();
+        idle.tick();
+        assert_eq!(idle.remaining.get(), 3);
+
+        // Navigation keys and the like are stopped by the key handler
+        let propagation = reset_then_handle(Some(&idle), || Propagation::Stop);
+
+        assert_eq!(propagation, Propagation::Stop);
+        assert_eq!(idle.remaining.get(), 5);
+        assert_eq!(shown.get(), 5);
+    }
+
+    #[test]
+    fn key_press_postpones_timeout_action() {
+        let idle = IdleCountdown::new(2, |_| {});
+
+        assert!(!idle.tick());
+        reset_then_handle(Some(&idle), || Propagation::Stop);
+
+        // The timeout action only runs once the countdown elapses without input
+        assert!(!idle.tick());
+        assert!(idle.tick());
+    }
 }